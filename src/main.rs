@@ -1,16 +1,123 @@
-use std::time::{Duration, SystemTime};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime},
+};
 
+use cgmath::{EuclideanSpace, InnerSpace, Zero};
 use glfw::{fail_on_errors, Action, Context, Key, Window};
 use rayon::prelude::*;
 mod renderer_backend;
 use renderer_backend::{
-    bind_group, bind_group_layout, camera, instance,
+    bind_group, bind_group_layout, bloom, camera, camera_path, capture, console, cubemap,
+    debug_draw, debug_ui, debug_view, environment, frame_graph, gpu_timer, hdr, instance, light,
+    mesher,
+    metrics::{self, Phase},
     model::{self, Vertex},
-    pipeline, texture,
+    pipeline, post_process,
+    reflection::ShaderReflection,
+    screenshot, sky, text, texture, time_of_day, transparency, world,
 };
 use wgpu::util::DeviceExt;
 
 pub const RESSOURCES_DIR: &str = "res";
+// at startup, or the highest supported below it, changed with the msaa command
+const MSAA_SAMPLE_COUNT: u32 = 4;
+const FAR_PLANE: f32 = 1000.0;
+const DAY_LENGTH: Duration = Duration::from_secs(20 * 60);
+const WORLD_SEED: u32 = 1337;
+const SUPERSAMPLED_SCREENSHOT_SCALE: u32 = 4;
+const CAPTURE_FRAME_RATE: u32 = 60;
+const CAMERA_PATH_FILE: &str = "camera_path.txt";
+// saved with the daytime command, restored at startup
+const TIME_OF_DAY_FILE: &str = "time_of_day.txt";
+const METRICS_DIR: &str = "metrics";
+// render passes timed on the GPU
+const GPU_PASSES: &[&str] = &["scene", "oit"];
+const FONT_FILE: &str = "monospace.png";
+// instances further from the camera get no debug bounding sphere
+const BOUNDING_SPHERE_DISTANCE: f32 = 12.0;
+// console commands run at startup when present, one per line
+const STARTUP_SCRIPT: &str = "startup.cfg";
+// scripts running scripts, at most this deep
+const MAX_SCRIPT_DEPTH: u32 = 8;
+
+const COMMANDS: &[console::Command] = {
+    use console::{ArgKind, Command, Param};
+    &[
+        Command {
+            name: "help",
+            params: &[],
+            help: "lists the commands",
+        },
+        Command {
+            name: "clear",
+            params: &[],
+            help: "clears the console",
+        },
+        Command {
+            name: "tp",
+            params: &[
+                Param::required("x", ArgKind::Float),
+                Param::required("y", ArgKind::Float),
+                Param::required("z", ArgKind::Float),
+            ],
+            help: "moves the camera",
+        },
+        Command {
+            name: "fov",
+            params: &[Param::required("degrees", ArgKind::Float)],
+            help: "vertical field of view",
+        },
+        Command {
+            name: "speed",
+            params: &[Param::required("speed", ArgKind::Float)],
+            help: "camera speed, in units per second",
+        },
+        Command {
+            name: "time",
+            params: &[Param::required("hh:mm", ArgKind::Time)],
+            help: "moves the time of day forward to the given time",
+        },
+        Command {
+            name: "daytime",
+            params: &[Param::required("action", ArgKind::Word(&["save", "load"]))],
+            help: "saves or restores the time of day, in time_of_day.txt",
+        },
+        Command {
+            name: "regen",
+            params: &[Param::required("seed", ArgKind::Int)],
+            help: "generates the world again",
+        },
+        Command {
+            name: "screenshot",
+            params: &[Param::optional("scale", ArgKind::Int)],
+            help: "saves the next frame, rendered at scale times the window resolution",
+        },
+        Command {
+            name: "pipeline",
+            params: &[Param::required("action", ArgKind::Word(&["reload"]))],
+            help: "builds the scene pipelines again from shaders/shader.wgsl",
+        },
+        Command {
+            name: "set",
+            params: &[
+                Param::required("setting", ArgKind::Word(&["vsync"])),
+                Param::required("value", ArgKind::Bool),
+            ],
+            help: "changes a setting",
+        },
+        Command {
+            name: "msaa",
+            params: &[Param::required("samples", ArgKind::Int)],
+            help: "multisampling of the scene, 1 turning it off",
+        },
+        Command {
+            name: "exec",
+            params: &[Param::required("file", ArgKind::Word(&[]))],
+            help: "runs the commands of a file",
+        },
+    ]
+};
 
 struct State<'a> {
     instance: wgpu::Instance,
@@ -20,15 +127,58 @@ struct State<'a> {
     config: wgpu::SurfaceConfiguration,
     size: (i32, i32),
     window: &'a mut Window,
-    render_pipeline: wgpu::RenderPipeline,
+    // supported by the surface, for the vsync setting
+    present_modes: Vec<wgpu::PresentMode>,
+    scene_pipelines: ScenePipelines,
+    oit: transparency::WeightedBlendedOit,
+    transparency_mode: transparency::TransparencyMode,
     obj_model: model::Model,
-    face_texture: texture::Texture,
+    // one per instance::RenderLayer, the opaque one also used by the chunks
+    layer_textures: [texture::Texture; 3],
     camera: camera::Camera,
     camera_projection: camera::Projection,
     camera_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    environment_bind_group_layout: wgpu::BindGroupLayout,
+    // every permutation of the scene shader, the layouts above being reflected from them
+    scene_reflections: Vec<ShaderReflection>,
     camera_controller: camera::CameraController,
+    camera_path: camera_path::CameraPath,
     instances: Vec<instance::Instance>,
+    world: world::World,
+    chunk_meshes: HashMap<world::ChunkPos, mesher::ChunkMesh>,
     depth_texture: texture::Texture,
+    sample_count: u32,
+    // supported by every multisampled target
+    sample_counts: Vec<u32>,
+    multisampled_texture: Option<texture::Texture>,
+    hdr: hdr::HdrPipeline,
+    bloom: bloom::BloomPipeline,
+    sky: sky::Sky,
+    environment: environment::Environment,
+    time_of_day: time_of_day::TimeOfDay,
+    fog: environment::Fog,
+    post_process: post_process::PostProcessChain,
+    delta_time: Duration,
+    // taken at the end of the next frame, at this times the window resolution
+    screenshot_scale: Option<u32>,
+    capture: Option<capture::FrameCapture>,
+    gpu_timer: gpu_timer::GpuTimer,
+    metrics: metrics::Metrics,
+    frame_graph: frame_graph::FrameGraph,
+    text: text::TextRenderer,
+    debug_ui: debug_ui::DebugUi,
+    debug_draw: debug_draw::DebugDraw,
+    debug_layers: debug_draw::DebugLayers,
+    // culls instead of the camera frustum while set
+    frozen_frustum: Option<camera::Frustum>,
+    // draws the instances culled by the frozen frustum in a flashy color
+    tint_culled: bool,
+    debug: debug_view::DebugState,
+    blit: screenshot::Blit,
+    console: console::Console,
+    // scripts being run, see MAX_SCRIPT_DEPTH
+    script_depth: u32,
 }
 
 impl<'a> State<'a> {
@@ -52,7 +202,11 @@ impl<'a> State<'a> {
         let adapter = instance.request_adapter(&adapter_descriptor).await.unwrap();
 
         let device_descriptor = wgpu::DeviceDescriptor {
-            required_features: wgpu::Features::empty(),
+            // the adapter specific features allowing more sample counts than 1 and 4
+            required_features: adapter.features()
+                & (wgpu::Features::POLYGON_MODE_LINE
+                    | wgpu::Features::TIMESTAMP_QUERY
+                    | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES),
             required_limits: wgpu::Limits::default(),
             label: Some("Device"),
             memory_hints: wgpu::MemoryHints::MemoryUsage,
@@ -72,7 +226,9 @@ impl<'a> State<'a> {
             .next()
             .unwrap_or(surface_capabilities.formats[0]);
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // copied from for screenshots when supported
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_capabilities.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.0 as u32,
             height: size.1 as u32,
@@ -84,47 +240,136 @@ impl<'a> State<'a> {
         surface.configure(&device, &config);
 
         let camera = camera::Camera::new((-5.0, 5.0, -5.0), cgmath::Deg(45.0), cgmath::Deg(0.0));
-        let camera_projection =
-            camera::Projection::new(config.width, config.height, cgmath::Deg(90.0), 0.1, 1000.0);
+        let camera_projection = camera::Projection::new(
+            config.width,
+            config.height,
+            cgmath::Deg(90.0),
+            0.1,
+            FAR_PLANE,
+        );
         let camera_controller =
             camera::CameraController::new(std::f32::consts::PI, 0.1, window.get_cursor_pos());
 
-        let camera_bind_group_layout = {
-            let mut builder = bind_group_layout::Builder::new(&device);
-            builder.add_buffer(wgpu::ShaderStages::VERTEX);
-            builder.build("Camera Bind Group Layout")
-        };
-
-        let texture_bind_group_layout = texture::Texture::default_layout(&device);
+        let sample_counts = texture::Texture::supported_sample_counts(
+            &adapter,
+            device.features(),
+            &[
+                hdr::HDR_FORMAT,
+                texture::Texture::DEPTH_FORMAT,
+                transparency::ACCUM_FORMAT,
+                transparency::REVEALAGE_FORMAT,
+            ],
+        );
+        let sample_count = sample_counts
+            .iter()
+            .copied()
+            .filter(|count| *count <= MSAA_SAMPLE_COUNT)
+            .max()
+            .unwrap_or(1);
 
-        let depth_texture = texture::Texture::create_depth_texture(&device, &config);
+        let scene_reflections = ScenePipelines::reflect(&device, sample_count)
+            .unwrap_or_else(|message| panic!("{}", message));
 
-        let render_pipeline = {
-            let mut builder = pipeline::Builder::new(&device);
-            builder.add_vertex_buffer_layout(model::ModelVertex::desc());
-            builder.add_vertex_buffer_layout(instance::InstanceRaw::desc());
-            builder.set_shader_module("shaders/shader.wgsl", "vertex_main", "fragment_main");
-            builder.set_pixel_format(config.format);
-            builder.set_front_face(wgpu::FrontFace::Cw);
-            builder.add_bind_group_layout(&camera_bind_group_layout);
-            builder.add_bind_group_layout(&texture_bind_group_layout);
-            builder.build_pipeline("Render Pipeline")
-        };
+        let [camera_bind_group_layout, texture_bind_group_layout, environment_bind_group_layout] =
+            create_scene_layouts(&device, &scene_reflections)
+                .unwrap_or_else(|message| panic!("{}", message));
+        let environment = environment::Environment::new(
+            &device,
+            &environment_bind_group_layout,
+            &scene_reflections,
+            2,
+        );
+        let depth_texture = texture::Texture::create_depth_texture(&device, &config, sample_count);
+        let multisampled_texture = (sample_count > 1).then(|| {
+            texture::Texture::create_multisampled_texture(
+                &device,
+                &config,
+                hdr::HDR_FORMAT,
+                sample_count,
+            )
+        });
+        let hdr = hdr::HdrPipeline::new(&device, config.width, config.height, config.format);
+        let bloom = bloom::BloomPipeline::new(&device, &hdr.target);
 
-        let stone_image = texture::Texture::load_image("test.png");
-        let face_texture = texture::Texture::from_image(
-            &stone_image,
+        let mut post_process = post_process::PostProcessChain::new(
             &device,
+            config.width,
+            config.height,
+            hdr::HDR_FORMAT,
+        );
+        post_process.add_effect(&device, "Vignette", "shaders/post/vignette.wgsl");
+        post_process.set_uniforms(
             &queue,
-            Some(&texture_bind_group_layout),
-            Some("stone"),
+            "Vignette",
+            &post_process::VignetteUniform {
+                intensity: 0.6,
+                radius: 0.5,
+                softness: 0.6,
+                _padding: 0.0,
+            },
         );
+        post_process.add_effect(&device, "Sharpen", "shaders/post/sharpen.wgsl");
+        post_process.set_uniforms(
+            &queue,
+            "Sharpen",
+            &post_process::SharpenUniform {
+                strength: 0.2,
+                _padding: [0.0; 3],
+            },
+        );
+        post_process.set_enabled("Sharpen", false);
+
+        let mut sky = sky::Sky::new(&device, &camera_bind_group_layout, sample_count);
+        if let Some(skybox) = cubemap::load(&device, &queue, "skybox", 1024) {
+            sky.set_cubemap(&device, skybox);
+        }
+
+        let scene_layouts = [
+            &camera_bind_group_layout,
+            &texture_bind_group_layout,
+            &environment_bind_group_layout,
+        ];
+        let scene_pipelines = ScenePipelines::new(&device, &scene_layouts, sample_count)
+            .unwrap_or_else(|message| panic!("{}", message));
+        let debug = debug_view::DebugState::new(&device, config.format);
+        let blit = screenshot::Blit::new(&device, config.format);
+        let oit =
+            transparency::WeightedBlendedOit::new(&device, &config, hdr::HDR_FORMAT, sample_count);
+
+        let load_texture = |filename: &str, label: &str| {
+            texture::Texture::from_image_with_options(
+                &texture::Texture::load_image(filename),
+                &device,
+                &queue,
+                Some(&texture_bind_group_layout),
+                Some(label),
+                &texture::TextureOptions {
+                    generate_mipmaps: true,
+                    ..Default::default()
+                },
+            )
+        };
+        // indexed by instance::RenderLayer
+        let layer_textures = [
+            load_texture("test.png", "stone"),
+            load_texture("leaves.png", "leaves"),
+            load_texture("glass.png", "glass"),
+        ];
 
         let simple_block = model::Model::load_model("full_block.obj", &device, &queue);
 
         // let instances = vec![instance::Instance::default_instance()];
         let instances = instance::Instance::test_instances(75, 3, false, 1.0);
 
+        let (world, chunk_meshes) = generate_world(&device, WORLD_SEED);
+
+        let gpu_timer = gpu_timer::GpuTimer::new(&device, &queue, GPU_PASSES.len() as u32);
+        let frame_graph = frame_graph::FrameGraph::new(&device, config.format);
+        let text = text::TextRenderer::new(&device, &queue, config.format, FONT_FILE);
+        let debug_ui = debug_ui::DebugUi::new(&device, config.format);
+        let debug_draw =
+            debug_draw::DebugDraw::new(&device, &camera_bind_group_layout, sample_count);
+
         Self {
             instance,
             surface,
@@ -133,16 +378,525 @@ impl<'a> State<'a> {
             config,
             size,
             window,
-            render_pipeline,
+            present_modes: surface_capabilities.present_modes.clone(),
+            scene_pipelines,
+            oit,
+            transparency_mode: transparency::TransparencyMode::Sorted,
             obj_model: simple_block,
-            face_texture,
+            layer_textures,
             camera,
             camera_projection,
             camera_bind_group_layout,
+            texture_bind_group_layout,
+            environment_bind_group_layout,
+            scene_reflections,
             camera_controller,
+            camera_path: camera_path::CameraPath::load(std::path::Path::new(CAMERA_PATH_FILE))
+                .unwrap_or_default(),
             instances: instances,
+            world,
+            chunk_meshes,
             depth_texture,
+            sample_count,
+            sample_counts,
+            multisampled_texture,
+            hdr,
+            bloom,
+            sky,
+            environment,
+            time_of_day: time_of_day::TimeOfDay::load(
+                std::path::Path::new(TIME_OF_DAY_FILE),
+                DAY_LENGTH,
+            )
+            .unwrap_or_else(|_| time_of_day::TimeOfDay::new(DAY_LENGTH)),
+            fog: environment::Fog::default(),
+            post_process,
+            delta_time: Duration::ZERO,
+            screenshot_scale: None,
+            capture: None,
+            gpu_timer,
+            metrics: metrics::Metrics::new(GPU_PASSES),
+            frame_graph,
+            text,
+            debug_ui,
+            debug_draw,
+            debug_layers: debug_draw::DebugLayers {
+                axes: true,
+                grid: false,
+                chunk_borders: true,
+                bounding_spheres: true,
+            },
+            frozen_frustum: None,
+            tint_culled: true,
+            debug,
+            blit,
+            console: console::Console::new(COMMANDS),
+            script_depth: 0,
+        }
+    }
+
+    // nothing is visible past the fog, so there is no need to render it
+    fn update_far_plane(&mut self) {
+        let zfar = self
+            .fog
+            .visibility_distance()
+            .map_or(FAR_PLANE, |distance| distance.min(FAR_PLANE));
+        self.camera_projection.set_zfar(zfar);
+    }
+
+    // relights and remeshes the chunks around the block
+    fn set_block(&mut self, x: i32, y: i32, z: i32, block: world::Block) {
+        for chunk_pos in light::set_block(&mut self.world, x, y, z, block) {
+            let mesh = mesher::build_chunk_mesh(&self.world, chunk_pos);
+            match mesher::ChunkMesh::new(&self.device, &mesh, "Chunk") {
+                Some(chunk_mesh) => self.chunk_meshes.insert(chunk_pos, chunk_mesh),
+                None => self.chunk_meshes.remove(&chunk_pos),
+            };
+        }
+    }
+
+    fn stop_capture(&mut self) {
+        if let Some(capture) = self.capture.take() {
+            self.console.print(format!(
+                "Capture stopped: {} frames in {}",
+                capture.frame_count(),
+                capture.directory.display()
+            ));
+        }
+    }
+
+    // starts recording every frame to a new directory, or stops the current recording
+    fn toggle_capture(&mut self, format: capture::CaptureFormat) {
+        match self.capture {
+            Some(_) => self.stop_capture(),
+            None => {
+                let directory = std::path::Path::new(capture::CAPTURE_DIR)
+                    .join(format!("capture_{}", screenshot::timestamp()));
+                let capture = capture::FrameCapture::new(
+                    &directory,
+                    format,
+                    CAPTURE_FRAME_RATE,
+                    self.config.width,
+                    self.config.height,
+                );
+                match capture {
+                    Ok(capture) => {
+                        self.console.print(format!(
+                            "Capturing {:?} at {} fps to {}",
+                            format,
+                            CAPTURE_FRAME_RATE,
+                            directory.display()
+                        ));
+                        self.capture = Some(capture);
+                    }
+                    Err(e) => self.console.print(format!(
+                        "cannot start the capture in {}: {}",
+                        directory.display(),
+                        e
+                    )),
+                }
+            }
+        }
+    }
+
+    fn update(&mut self, delta_time: Duration) {
+        self.delta_time = delta_time;
+        self.time_of_day.update(delta_time);
+        if self.camera_path.is_playing() {
+            self.camera_path.update(&mut self.camera, delta_time);
+        } else {
+            self.camera_controller
+                .update_camera(&mut self.camera, delta_time);
+        }
+    }
+
+    // one CSV row per frame until toggled again
+    fn toggle_metrics_log(&mut self) {
+        if let Some(path) = self.metrics.csv_path() {
+            let message = format!("Metrics saved to {}", path.display());
+            match self.metrics.stop_csv() {
+                Ok(()) => self.console.print(message),
+                Err(e) => self.console.print(e),
+            }
+        } else {
+            let path =
+                screenshot::timestamped_path(std::path::Path::new(METRICS_DIR), "metrics", "csv");
+            match self.metrics.start_csv(&path) {
+                Ok(()) => self
+                    .console
+                    .print(format!("Logging metrics to {}", path.display())),
+                Err(e) => {
+                    self.console
+                        .print(format!("cannot log metrics to {}: {}", path.display(), e))
+                }
+            }
+        }
+    }
+
+    // culling keeps using the current frustum while the camera moves away from it
+    fn toggle_frozen_frustum(&mut self) {
+        self.frozen_frustum = match self.frozen_frustum {
+            Some(_) => None,
+            None => Some(camera::Frustum::new(&self.camera, &self.camera_projection)),
+        };
+    }
+
+    // the camera stays where it was while the cursor is free
+    fn toggle_debug_ui(&mut self) {
+        self.debug_ui.toggle(self.window);
+        self.camera_controller
+            .reset_cursor(self.window.get_cursor_pos());
+    }
+
+    fn inspector(&mut self, context: &egui::Context) {
+        egui::Window::new("Inspector")
+            .default_pos([10.0, 120.0])
+            .show(context, |ui| {
+                egui::CollapsingHeader::new("Camera")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        let position = self.camera.position;
+                        ui.label(format!(
+                            "position {:.1} {:.1} {:.1}",
+                            position.x, position.y, position.z
+                        ));
+                        let mut fovy = cgmath::Deg::from(self.camera_projection.fovy()).0;
+                        if ui
+                            .add(egui::Slider::new(&mut fovy, 10.0..=150.0).text("fov"))
+                            .changed()
+                        {
+                            self.camera_projection.set_fovy(cgmath::Deg(fovy));
+                        }
+                        let mut znear = self.camera_projection.znear();
+                        if ui
+                            .add(
+                                egui::Slider::new(&mut znear, 0.01..=10.0)
+                                    .logarithmic(true)
+                                    .text("znear"),
+                            )
+                            .changed()
+                        {
+                            self.camera_projection.set_znear(znear);
+                        }
+                        // lowered again by the fog when it changes
+                        let mut zfar = self.camera_projection.zfar();
+                        if ui
+                            .add(
+                                egui::Slider::new(&mut zfar, 10.0..=FAR_PLANE)
+                                    .logarithmic(true)
+                                    .text("zfar"),
+                            )
+                            .changed()
+                        {
+                            self.camera_projection.set_zfar(zfar);
+                        }
+                        let mut speed = self.camera_controller.speed();
+                        if ui
+                            .add(egui::Slider::new(&mut speed, 0.5..=100.0).text("speed"))
+                            .changed()
+                        {
+                            self.camera_controller.set_speed(speed);
+                        }
+                    });
+
+                egui::CollapsingHeader::new("Instances")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        let stats = self.metrics.history().back().cloned().unwrap_or_default();
+                        ui.label(format!(
+                            "{} visible of {}",
+                            stats.visible_instances,
+                            self.instances.len()
+                        ));
+                        ui.label(format!("{} chunk meshes", self.chunk_meshes.len()));
+                        let mut frozen = self.frozen_frustum.is_some();
+                        if ui.checkbox(&mut frozen, "freeze frustum").changed() {
+                            self.toggle_frozen_frustum();
+                        }
+                        ui.checkbox(&mut self.tint_culled, "show culled instances");
+                        ui.label(format!(
+                            "{} draw calls, {} triangles",
+                            stats.draw_calls, stats.triangles
+                        ));
+                    });
+
+                egui::CollapsingHeader::new("Lighting").show(ui, |ui| {
+                    let (hours, minutes) = self.time_of_day.clock();
+                    let mut time = self.time_of_day.time();
+                    if ui
+                        .add(
+                            egui::Slider::new(&mut time, 0.0..=1.0)
+                                .text(format!("{:02}:{:02}", hours, minutes)),
+                        )
+                        .changed()
+                    {
+                        self.time_of_day.scrub(time - self.time_of_day.time());
+                    }
+                    ui.checkbox(&mut self.time_of_day.paused, "time paused");
+                    ui.add(
+                        egui::Slider::new(&mut self.hdr.exposure, 0.01..=16.0)
+                            .logarithmic(true)
+                            .text("exposure"),
+                    );
+                    ui.checkbox(&mut self.hdr.auto_exposure, "auto exposure");
+                    debug_ui::DebugUi::enum_combo(
+                        ui,
+                        "tone mapping",
+                        &mut self.hdr.tone_mapping,
+                        hdr::ToneMapping::next,
+                    );
+                    if debug_ui::DebugUi::enum_combo(
+                        ui,
+                        "fog",
+                        &mut self.fog.mode,
+                        environment::FogMode::next,
+                    ) {
+                        self.update_far_plane();
+                    }
+                    if ui.button(format!("sky: {:?}", self.sky.mode)).clicked() {
+                        self.sky.next_mode();
+                    }
+                });
+
+                egui::CollapsingHeader::new("Pipeline").show(ui, |ui| {
+                    debug_ui::DebugUi::enum_combo(
+                        ui,
+                        "transparency",
+                        &mut self.transparency_mode,
+                        transparency::TransparencyMode::next,
+                    );
+                    ui.checkbox(&mut self.bloom.enabled, "bloom");
+                    for effect in &mut self.post_process.effects {
+                        ui.checkbox(&mut effect.enabled, effect.name.as_str());
+                    }
+                    ui.checkbox(&mut self.frame_graph.enabled, "frame graph");
+                });
+
+                egui::CollapsingHeader::new("Debug draw").show(ui, |ui| {
+                    ui.checkbox(&mut self.debug_draw.enabled, "enabled");
+                    ui.add_enabled_ui(self.debug_draw.enabled, |ui| {
+                        ui.checkbox(&mut self.debug_layers.axes, "world axes");
+                        ui.checkbox(&mut self.debug_layers.grid, "grid");
+                        ui.checkbox(&mut self.debug_layers.chunk_borders, "chunk borders");
+                        ui.checkbox(&mut self.debug_layers.bounding_spheres, "bounding spheres");
+                    });
+                    debug_ui::DebugUi::enum_combo(
+                        ui,
+                        "view",
+                        &mut self.debug.view,
+                        debug_view::DebugView::next,
+                    );
+                });
+            });
+    }
+
+    // a line typed in the console or read from a script
+    fn run_command(&mut self, line: &str) {
+        self.console.print(format!("> {}", line));
+        let (command, args) = match self.console.parse(line) {
+            Ok(parsed) => parsed,
+            Err(message) => {
+                self.console.print(message);
+                return;
+            }
+        };
+        match command.name {
+            "help" => {
+                for command in self.console.commands() {
+                    self.console
+                        .print(format!("{} - {}", command.usage(), command.help));
+                }
+            }
+            "clear" => self.console.clear(),
+            "tp" => {
+                self.camera.position =
+                    cgmath::point3(args[0].float(), args[1].float(), args[2].float());
+            }
+            "fov" => {
+                let fovy = args[0].float().clamp(10.0, 150.0);
+                self.camera_projection.set_fovy(cgmath::Deg(fovy));
+            }
+            "speed" => self.camera_controller.set_speed(args[0].float()),
+            "time" => {
+                let (hours, minutes) = args[0].time();
+                let time = (hours as f32 + minutes as f32 / 60.0) / 24.0;
+                // forward, the day count going on
+                self.time_of_day
+                    .scrub((time - self.time_of_day.time()).rem_euclid(1.0));
+            }
+            "daytime" => {
+                let path = std::path::Path::new(TIME_OF_DAY_FILE);
+                let result = if args[0].word() == "save" {
+                    self.time_of_day.save(path)
+                } else {
+                    time_of_day::TimeOfDay::load(path, DAY_LENGTH)
+                        .map(|time_of_day| self.time_of_day = time_of_day)
+                };
+                let (hours, minutes) = self.time_of_day.clock();
+                self.console.print(match result {
+                    Ok(()) => format!("Day {} {:02}:{:02}", self.time_of_day.day(), hours, minutes),
+                    Err(e) => format!("cannot {} {}: {}", args[0].word(), TIME_OF_DAY_FILE, e),
+                });
+            }
+            "regen" => {
+                let seed = args[0].int() as u32;
+                (self.world, self.chunk_meshes) = generate_world(&self.device, seed);
+                self.console.print(format!(
+                    "{} chunk meshes generated with seed {}",
+                    self.chunk_meshes.len(),
+                    seed
+                ));
+            }
+            "screenshot" => {
+                let scale = args.first().map_or(1, |scale| scale.int().max(1) as u32);
+                self.screenshot_scale = Some(scale);
+            }
+            "pipeline" => self.reload_scene_pipelines(),
+            // vsync being the only setting
+            "set" => self.set_vsync(args[1].bool()),
+            "msaa" => self.set_sample_count(args[0].int().max(1) as u32),
+            "exec" => self.run_script(std::path::Path::new(args[0].word())),
+            _ => self
+                .console
+                .print(format!("{} does nothing yet", command.name)),
+        }
+    }
+
+    fn run_script(&mut self, path: &std::path::Path) {
+        if self.script_depth == MAX_SCRIPT_DEPTH {
+            self.console.print(format!(
+                "not running {}, too many nested scripts",
+                path.display()
+            ));
+            return;
+        }
+        match console::Console::read_script(path) {
+            Ok(lines) => {
+                self.script_depth += 1;
+                for line in lines {
+                    self.run_command(&line);
+                }
+                self.script_depth -= 1;
+            }
+            Err(e) => self
+                .console
+                .print(format!("cannot read {}: {}", path.display(), e)),
+        }
+    }
+
+    // the previous pipelines are kept when the shader has an error
+    fn reload_scene_pipelines(&mut self) {
+        match self.try_reload_scene_pipelines() {
+            Ok(()) => self.console.print("Scene pipelines reloaded"),
+            Err(message) => self.console.print(message),
+        }
+    }
+
+    // the layouts are reflected again, the bindings of the shader being free to change, and
+    // what is bound with them is created again
+    fn try_reload_scene_pipelines(&mut self) -> Result<(), String> {
+        let reflections = ScenePipelines::reflect(&self.device, self.sample_count)?;
+        let [camera_layout, texture_layout, environment_layout] =
+            create_scene_layouts(&self.device, &reflections)?;
+        let layouts = [&camera_layout, &texture_layout, &environment_layout];
+        self.scene_pipelines = ScenePipelines::new(&self.device, &layouts, self.sample_count)?;
+        self.debug.clear_pipelines();
+
+        self.environment
+            .set_layout(&self.device, &environment_layout, &reflections, 2);
+        for texture in &mut self.layer_textures {
+            texture.bind_group =
+                Some(texture.create_bind_group(&self.device, &texture_layout, "Layer Texture"));
+        }
+        self.sky
+            .set_sample_count(&self.device, &camera_layout, self.sample_count);
+        self.debug_draw
+            .set_sample_count(&self.device, &camera_layout, self.sample_count);
+        self.camera_bind_group_layout = camera_layout;
+        self.texture_bind_group_layout = texture_layout;
+        self.environment_bind_group_layout = environment_layout;
+        self.scene_reflections = reflections;
+        Ok(())
+    }
+
+    // every multisampled target and pipeline is created again, the previous ones being kept
+    // when the scene shader has an error
+    fn set_sample_count(&mut self, sample_count: u32) {
+        if !self.sample_counts.contains(&sample_count) {
+            let supported = self
+                .sample_counts
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>();
+            self.console.print(format!(
+                "{} samples are not supported, try {}",
+                sample_count,
+                supported.join(", ")
+            ));
+            return;
         }
+        let layouts = [
+            &self.camera_bind_group_layout,
+            &self.texture_bind_group_layout,
+            &self.environment_bind_group_layout,
+        ];
+        match ScenePipelines::new(&self.device, &layouts, sample_count) {
+            Ok(scene_pipelines) => self.scene_pipelines = scene_pipelines,
+            Err(message) => {
+                self.console.print(message);
+                return;
+            }
+        }
+        self.debug.clear_pipelines();
+        self.sample_count = sample_count;
+        self.sky
+            .set_sample_count(&self.device, &self.camera_bind_group_layout, sample_count);
+        self.debug_draw.set_sample_count(
+            &self.device,
+            &self.camera_bind_group_layout,
+            sample_count,
+        );
+        self.oit.set_sample_count(sample_count);
+        self.resize_targets(self.config.width, self.config.height);
+        self.console
+            .print(format!("Multisampling: {} samples", sample_count));
+    }
+
+    // without vsync, frames are presented as soon as they are ready, tearing or not
+    fn set_vsync(&mut self, vsync: bool) {
+        let present_mode = if vsync {
+            Some(wgpu::PresentMode::Fifo)
+        } else {
+            [wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox]
+                .into_iter()
+                .find(|mode| self.present_modes.contains(mode))
+        };
+        match present_mode {
+            Some(present_mode) => {
+                self.config.present_mode = present_mode;
+                self.surface.configure(&self.device, &self.config);
+                self.console
+                    .print(format!("Present mode: {:?}", present_mode));
+            }
+            None => self
+                .console
+                .print("vsync cannot be turned off on this surface"),
+        }
+    }
+
+    fn save_camera_path(&mut self) {
+        let message = match self
+            .camera_path
+            .save(std::path::Path::new(CAMERA_PATH_FILE))
+        {
+            Ok(()) => format!(
+                "Camera path: {} keyframes, {}s",
+                self.camera_path.keyframes().len(),
+                self.camera_path.duration()
+            ),
+            Err(e) => format!("cannot save {}: {}", CAMERA_PATH_FILE, e),
+        };
+        self.console.print(message);
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -156,16 +910,159 @@ impl<'a> State<'a> {
         let mut command_encoder = self
             .device
             .create_command_encoder(&command_encoder_descriptor);
+        // the frames of a capture all have the window size, screenshots included
+        let screenshot_scale = self.screenshot_scale.take();
+        let read_scale = if self.capture.is_some() {
+            Some(1)
+        } else {
+            screenshot_scale
+        };
+        // rendered offscreen then drawn on the surface when it cannot be copied from, or
+        // when supersampling
+        let surface_readable = drawable
+            .texture
+            .usage()
+            .contains(wgpu::TextureUsages::COPY_SRC);
+        let offscreen = read_scale
+            .filter(|&scale| scale > 1 || !surface_readable)
+            .map(|scale| self.create_offscreen_texture(scale));
+        match &offscreen {
+            Some(texture) => {
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                self.encode_frame(&mut command_encoder, &view);
+                self.blit
+                    .draw(&self.device, &mut command_encoder, &view, &image_view);
+            }
+            None => self.encode_frame(&mut command_encoder, &image_view),
+        }
+        self.frame_graph.update(&self.queue, &self.metrics);
+        self.frame_graph.draw(&mut command_encoder, &image_view);
+        // both at the top of the screen
+        if self.frame_graph.enabled && !self.console.is_open() {
+            let summary = self.metrics.summary();
+            // shadowed to stay readable over bright scenes
+            self.text
+                .add_text(&summary, (11.0, 11.0), 1.0, [0.0, 0.0, 0.0, 0.8]);
+            self.text
+                .add_text(&summary, (10.0, 10.0), 1.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+        self.console.draw(&mut self.text);
+        self.text.draw(
+            &self.device,
+            &self.queue,
+            &mut command_encoder,
+            &image_view,
+            (self.config.width, self.config.height),
+        );
+        if self.debug_ui.is_open() {
+            let context = self.debug_ui.begin_frame(self.window);
+            self.inspector(&context);
+            self.debug_ui.end_frame();
+        }
+        self.debug_ui.draw(
+            &self.device,
+            &self.queue,
+            &mut command_encoder,
+            &image_view,
+            (self.config.width, self.config.height),
+        );
+        let submit_start = Instant::now();
+        self.queue.submit(std::iter::once(command_encoder.finish()));
+        self.metrics
+            .add_phase(Phase::Submit, submit_start.elapsed());
+        self.gpu_timer.end_frame();
 
+        if read_scale.is_some() {
+            let frame = offscreen.as_ref().unwrap_or(&drawable.texture);
+            let image = screenshot::read_texture(&self.device, &self.queue, frame);
+            if frame.width() != self.config.width {
+                self.resize_targets(self.config.width, self.config.height);
+            }
+            match image {
+                Ok(image) => {
+                    if screenshot_scale.is_some() {
+                        self.save_screenshot(&image);
+                    }
+                    let written = self
+                        .capture
+                        .as_mut()
+                        .map(|capture| capture.write_frame(&image));
+                    if let Some(Err(e)) = written {
+                        self.console
+                            .print(format!("cannot write the captured frame: {}", e));
+                        self.stop_capture();
+                    }
+                }
+                Err(message) => self
+                    .console
+                    .print(format!("cannot read the frame: {}", message)),
+            }
+        }
+
+        let present_start = Instant::now();
+        drawable.present();
+        self.metrics
+            .add_phase(Phase::Present, present_start.elapsed());
+
+        Ok(())
+    }
+
+    // those of the selected debug view, unless built since it was last selected
+    fn build_debug_view_pipelines(&mut self) -> Result<(), String> {
+        let layouts = [
+            &self.camera_bind_group_layout,
+            &self.texture_bind_group_layout,
+            &self.environment_bind_group_layout,
+        ];
+        let (device, sample_count) = (&self.device, self.sample_count);
+        self.debug.build_pipelines(|| {
+            [
+                scene_pipeline_builder(device, &layouts, sample_count),
+                voxel_pipeline_builder(device, &layouts, sample_count),
+            ]
+        })
+    }
+
+    // the whole frame, tone mapped into `output` which has the surface format and the size
+    // of the render targets
+    fn encode_frame(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+    ) {
+        self.gpu_timer.begin_frame(&self.device);
+        if let Err(message) = self.build_debug_view_pipelines() {
+            self.console.print(message);
+            self.debug.view = debug_view::DebugView::Lit;
+        }
+        let upload_start = Instant::now();
+        let lighting = self.time_of_day.lighting();
+        self.environment.update(&self.queue, &lighting, &self.fog);
+        self.sky.set_lighting(&lighting);
+        self.sky.update(&self.queue);
+
+        // the scene is drawn into the HDR target, resolving it first when multisampling
+        let (view, resolve_target) = match &self.multisampled_texture {
+            Some(multisampled_texture) => (&multisampled_texture.view, Some(&self.hdr.target.view)),
+            None => (&self.hdr.target.view, None),
+        };
         let color_attachment = wgpu::RenderPassColorAttachment {
-            view: &image_view,
-            resolve_target: None,
+            view,
+            resolve_target,
             ops: wgpu::Operations {
-                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                store: wgpu::StoreOp::Store,
+                load: wgpu::LoadOp::Clear(if self.debug.view.replaces_shading() {
+                    wgpu::Color::BLACK
+                } else {
+                    lighting.clear_color()
+                }),
+                store: if resolve_target.is_some() {
+                    wgpu::StoreOp::Discard
+                } else {
+                    wgpu::StoreOp::Store
+                },
             },
         };
-        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+        let mut render_pass_descriptor = wgpu::RenderPassDescriptor {
             label: Some("Renderpass"),
             color_attachments: &[Some(color_attachment)],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
@@ -193,19 +1090,108 @@ impl<'a> State<'a> {
 
         let camera_bind_group = {
             let mut builder = bind_group::Builder::new(&self.device);
-            builder.add_buffer(&camera_buffer);
-            builder.set_layout(&self.camera_bind_group_layout);
+            builder.set_reflected_layout(
+                &self.camera_bind_group_layout,
+                &self.scene_reflections,
+                0,
+            );
+            builder.bind("camera", camera_buffer.as_entire_binding());
             builder.build("Camera Bind Group")
         };
+        self.metrics
+            .add_phase(Phase::Upload, upload_start.elapsed());
 
-        let frustum = camera::Frustum::new(&self.camera, &self.camera_projection);
-        let instance_data = self
-            .instances
+        let culling_start = Instant::now();
+        let camera_frustum = camera::Frustum::new(&self.camera, &self.camera_projection);
+        let frustum = self.frozen_frustum.as_ref().unwrap_or(&camera_frustum);
+        let (visible_instances, culled_instances): (Vec<_>, Vec<_>) =
+            if self.frozen_frustum.is_some() && self.tint_culled {
+                self.instances
+                    .par_iter()
+                    .partition(|instance| frustum.is_inside_instance(instance))
+            } else {
+                let visible_instances = self
+                    .instances
+                    .par_iter()
+                    .filter(|instance| frustum.is_inside_instance(instance))
+                    .collect();
+                (visible_instances, Vec::new())
+            };
+        // grouped by layer in drawing order, translucent ones back to front when sorted
+        let mut layers: [Vec<&instance::Instance>; 3] = Default::default();
+        for instance in visible_instances {
+            layers[instance.layer as usize].push(instance);
+        }
+        if self.transparency_mode == transparency::TransparencyMode::Sorted {
+            transparency::sort_back_to_front(
+                &mut layers[instance::RenderLayer::Translucent as usize],
+                self.camera.position,
+            );
+        }
+        let mut layer_ranges: [std::ops::Range<u32>; 3] = Default::default();
+        let mut layer_start = 0;
+        for (range, layer) in layer_ranges.iter_mut().zip(&layers) {
+            *range = layer_start..layer_start + layer.len() as u32;
+            layer_start = range.end;
+        }
+        let [opaque_range, cutout_range, translucent_range] = layer_ranges.clone();
+        // after every layer, whatever their own
+        let culled_range = layer_start..layer_start + culled_instances.len() as u32;
+        self.metrics
+            .add_phase(Phase::Culling, culling_start.elapsed());
+        self.metrics.set_visible_instances(layer_start);
+
+        if self.debug_draw.enabled {
+            let debug_layers = self.debug_layers;
+            let camera_position = self.camera.position.to_vec();
+            if debug_layers.axes {
+                self.debug_draw.axes(cgmath::Vector3::zero(), 2.0);
+            }
+            if debug_layers.grid {
+                let center =
+                    cgmath::vec3(camera_position.x.round(), 0.0, camera_position.z.round());
+                self.debug_draw.grid(center, 1.0, 16, [0.5, 0.5, 0.5, 1.0]);
+            }
+            if debug_layers.chunk_borders {
+                for chunk_pos in self.world.chunks.keys() {
+                    let (x, z) = chunk_pos.origin();
+                    let min = cgmath::vec3(x as f32, 0.0, z as f32);
+                    let size = cgmath::vec3(
+                        world::CHUNK_SIZE as f32,
+                        world::CHUNK_HEIGHT as f32,
+                        world::CHUNK_SIZE as f32,
+                    );
+                    self.debug_draw.aabb(min, min + size, debug_draw::YELLOW);
+                }
+            }
+            if debug_layers.bounding_spheres {
+                let kept = layers
+                    .iter()
+                    .flatten()
+                    .map(|instance| (instance, debug_draw::GREEN));
+                let culled = culled_instances
+                    .iter()
+                    .map(|instance| (instance, debug_draw::RED));
+                for (instance, color) in kept.chain(culled) {
+                    let (center, radius) = instance.bounding_sphere();
+                    if (center - camera_position).magnitude() < BOUNDING_SPHERE_DISTANCE {
+                        self.debug_draw.sphere(center, radius, color);
+                    }
+                }
+            }
+        }
+        if let Some(frozen_frustum) = &self.frozen_frustum {
+            self.debug_draw.frustum(frozen_frustum, debug_draw::WHITE);
+        }
+        self.debug_draw.upload(&self.device, &self.queue);
+
+        let upload_start = Instant::now();
+        let mut ordered_instances = layers.concat();
+        ordered_instances.extend(&culled_instances);
+        let instance_data = ordered_instances
             .par_iter()
-            .filter(|instance| frustum.is_inside_instance(instance))
-            .map(instance::Instance::raw)
+            .map(|instance| instance.raw())
             .collect::<Vec<_>>();
-        println!("Instance data: {:?}", instance_data.len());
         let instance_buffer = self
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -213,40 +1199,308 @@ impl<'a> State<'a> {
                 contents: bytemuck::cast_slice(&instance_data),
                 usage: wgpu::BufferUsages::VERTEX,
             });
+        self.metrics
+            .add_phase(Phase::Upload, upload_start.elapsed());
 
+        let encode_start = Instant::now();
+        // triangles of each draw call, counted once the passes are recorded
+        let mut draws: Vec<u64> = Vec::new();
+        let mesh_triangles = self.obj_model.meshes[0].num_elements as u64 / 3;
+        render_pass_descriptor.timestamp_writes = self.gpu_timer.pass_timestamps("scene");
         {
             let mut render_pass = command_encoder.begin_render_pass(&render_pass_descriptor);
-            render_pass.set_pipeline(&self.render_pipeline);
+            let debug_view_pipelines = self.debug.pipelines();
+            if let Some((instanced, voxel)) =
+                debug_view_pipelines.filter(|_| self.debug.view.replaces_shading())
+            {
+                // every layer alike, without the sky
+                render_pass.set_pipeline(instanced);
+                self.draw_visible_layers(
+                    &mut render_pass,
+                    &camera_bind_group,
+                    &instance_buffer,
+                    &layer_ranges,
+                    &mut draws,
+                );
+                render_pass.set_pipeline(voxel);
+                self.draw_chunks(&mut render_pass, &camera_bind_group, frustum, &mut draws);
+            } else {
+                render_pass.set_pipeline(&self.scene_pipelines.render);
+                self.draw_scene_layer(
+                    &mut render_pass,
+                    &camera_bind_group,
+                    &instance_buffer,
+                    instance::RenderLayer::Opaque,
+                    opaque_range.clone(),
+                );
+                draws.push(mesh_triangles * opaque_range.len() as u64);
 
-            render_pass.set_bind_group(0, &camera_bind_group, &[]);
-            render_pass.set_bind_group(1, self.face_texture.bind_group.as_ref().unwrap(), &[]);
+                if !culled_range.is_empty() {
+                    render_pass.set_pipeline(&self.scene_pipelines.culled);
+                    self.draw_scene_layer(
+                        &mut render_pass,
+                        &camera_bind_group,
+                        &instance_buffer,
+                        instance::RenderLayer::Opaque,
+                        culled_range.clone(),
+                    );
+                    draws.push(mesh_triangles * culled_range.len() as u64);
+                }
 
-            model::DrawModel::draw_mesh_instanced(
-                &mut render_pass,
-                &self.obj_model.meshes[0],
-                0..instance_data.len() as u32,
-                &instance_buffer,
-            );
+                render_pass.set_pipeline(&self.scene_pipelines.voxel);
+                self.draw_chunks(&mut render_pass, &camera_bind_group, frustum, &mut draws);
+
+                if !cutout_range.is_empty() {
+                    render_pass.set_pipeline(&self.scene_pipelines.cutout);
+                    self.draw_scene_layer(
+                        &mut render_pass,
+                        &camera_bind_group,
+                        &instance_buffer,
+                        instance::RenderLayer::Cutout,
+                        cutout_range.clone(),
+                    );
+                    draws.push(mesh_triangles * cutout_range.len() as u64);
+                }
+
+                self.sky.draw(&mut render_pass, &camera_bind_group);
+                draws.push(1);
+
+                if self.transparency_mode == transparency::TransparencyMode::Sorted
+                    && !translucent_range.is_empty()
+                {
+                    render_pass.set_pipeline(&self.scene_pipelines.translucent);
+                    self.draw_scene_layer(
+                        &mut render_pass,
+                        &camera_bind_group,
+                        &instance_buffer,
+                        instance::RenderLayer::Translucent,
+                        translucent_range.clone(),
+                    );
+                    draws.push(mesh_triangles * translucent_range.len() as u64);
+                }
+
+                if let Some((instanced, voxel)) = debug_view_pipelines {
+                    render_pass.set_pipeline(instanced);
+                    self.draw_visible_layers(
+                        &mut render_pass,
+                        &camera_bind_group,
+                        &instance_buffer,
+                        &layer_ranges,
+                        &mut draws,
+                    );
+                    render_pass.set_pipeline(voxel);
+                    self.draw_chunks(&mut render_pass, &camera_bind_group, frustum, &mut draws);
+                }
+            }
+
+            if self.debug_draw.has_lines() {
+                self.debug_draw.draw(&mut render_pass, &camera_bind_group);
+                draws.push(0);
+            }
         }
-        self.queue.submit(std::iter::once(command_encoder.finish()));
 
-        drawable.present();
+        if self.debug.view.replaces_shading() {
+            self.debug
+                .draw(&self.device, command_encoder, &self.hdr.target.view, output);
+        } else {
+            if self.transparency_mode == transparency::TransparencyMode::WeightedBlended
+                && !translucent_range.is_empty()
+            {
+                {
+                    let mut render_pass = self.oit.begin_accumulate_pass(
+                        command_encoder,
+                        &self.depth_texture.view,
+                        self.gpu_timer.pass_timestamps("oit"),
+                    );
+                    render_pass.set_pipeline(&self.scene_pipelines.oit);
+                    self.draw_scene_layer(
+                        &mut render_pass,
+                        &camera_bind_group,
+                        &instance_buffer,
+                        instance::RenderLayer::Translucent,
+                        translucent_range.clone(),
+                    );
+                    draws.push(mesh_triangles * translucent_range.len() as u64);
+                }
+                self.oit.composite(command_encoder, &self.hdr.target.view);
+            }
 
-        Ok(())
+            self.bloom.update(&self.queue);
+            self.bloom.apply(command_encoder, &self.hdr.target);
+            self.post_process.apply(command_encoder, &self.hdr.target);
+
+            self.hdr.update(&self.queue, self.delta_time);
+            self.hdr.compute_exposure(command_encoder);
+            self.hdr.tone_map(command_encoder, output);
+        }
+        self.gpu_timer.resolve(command_encoder);
+
+        for triangles in draws {
+            self.metrics.add_draw(triangles);
+        }
+        self.metrics
+            .add_phase(Phase::Encode, encode_start.elapsed());
+    }
+
+    fn save_screenshot(&mut self, image: &image::RgbaImage) {
+        let path = screenshot::timestamped_path(
+            std::path::Path::new(screenshot::SCREENSHOT_DIR),
+            "screenshot",
+            "png",
+        );
+        match screenshot::save(image, &path) {
+            Ok(()) => self.console.print(format!(
+                "Screenshot saved to {} ({}x{})",
+                path.display(),
+                image.width(),
+                image.height()
+            )),
+            Err(message) => self.console.print(message),
+        }
+    }
+
+    // the frame is rendered into it instead of the surface, at `scale` times the window
+    // resolution within the texture size limit, the render targets being resized to match
+    // until the frame is read
+    fn create_offscreen_texture(&mut self, scale: u32) -> wgpu::Texture {
+        let max_dimension = self.device.limits().max_texture_dimension_2d;
+        let scale = scale
+            .min(max_dimension / self.config.width.max(self.config.height))
+            .max(1);
+        let (width, height) = (self.config.width * scale, self.config.height * scale);
+        if scale > 1 {
+            self.resize_targets(width, height);
+        }
+
+        self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    // the pipeline is set by the caller
+    fn draw_scene_layer<'b>(
+        &'b self,
+        render_pass: &mut wgpu::RenderPass<'b>,
+        camera_bind_group: &wgpu::BindGroup,
+        instance_buffer: &wgpu::Buffer,
+        layer: instance::RenderLayer,
+        instances: std::ops::Range<u32>,
+    ) {
+        let texture = &self.layer_textures[layer as usize];
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, texture.bind_group.as_ref().unwrap(), &[]);
+        render_pass.set_bind_group(2, &self.environment.bind_group, &[]);
+
+        model::DrawModel::draw_mesh_instanced(
+            render_pass,
+            &self.obj_model.meshes[0],
+            instances,
+            instance_buffer,
+        );
+    }
+
+    // the pipeline is set by the caller, used by the debug views drawing every layer alike.
+    // Culled instances are left out, so they don't show up when the frustum is frozen
+    fn draw_visible_layers<'b>(
+        &'b self,
+        render_pass: &mut wgpu::RenderPass<'b>,
+        camera_bind_group: &wgpu::BindGroup,
+        instance_buffer: &wgpu::Buffer,
+        layer_ranges: &[std::ops::Range<u32>; 3],
+        draws: &mut Vec<u64>,
+    ) {
+        let mesh_triangles = self.obj_model.meshes[0].num_elements as u64 / 3;
+        for (layer, range) in instance::RenderLayer::ALL.into_iter().zip(layer_ranges) {
+            if !range.is_empty() {
+                self.draw_scene_layer(
+                    render_pass,
+                    camera_bind_group,
+                    instance_buffer,
+                    layer,
+                    range.clone(),
+                );
+                draws.push(mesh_triangles * range.len() as u64);
+            }
+        }
+    }
+
+    // the pipeline is set by the caller, chunks outside of `frustum` are skipped
+    fn draw_chunks<'b>(
+        &'b self,
+        render_pass: &mut wgpu::RenderPass<'b>,
+        camera_bind_group: &wgpu::BindGroup,
+        frustum: &camera::Frustum,
+        draws: &mut Vec<u64>,
+    ) {
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        let texture = &self.layer_textures[instance::RenderLayer::Opaque as usize];
+        render_pass.set_bind_group(1, texture.bind_group.as_ref().unwrap(), &[]);
+        render_pass.set_bind_group(2, &self.environment.bind_group, &[]);
+        for (chunk_pos, chunk_mesh) in &self.chunk_meshes {
+            let (x, z) = chunk_pos.origin();
+            let half_size = world::CHUNK_SIZE as f32 / 2.0;
+            let half_height = world::CHUNK_HEIGHT as f32 / 2.0;
+            let center = cgmath::vec3(x as f32 + half_size, half_height, z as f32 + half_size);
+            let radius = (2.0 * half_size * half_size + half_height * half_height).sqrt();
+            if frustum.is_inside_sphere(center, radius) {
+                chunk_mesh.draw(render_pass);
+                draws.push(chunk_mesh.num_elements as u64 / 3);
+            }
+        }
     }
 
     fn resize(&mut self, size: (i32, i32)) {
         if size.0 <= 0 || size.1 <= 0 {
             return;
         }
+        // every frame of a capture has the same size
+        if (size.0 as u32, size.1 as u32) != (self.config.width, self.config.height) {
+            self.stop_capture();
+        }
         self.size = size;
         self.config.width = size.0 as u32;
         self.config.height = size.1 as u32;
-        self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.config);
+        self.resize_targets(self.config.width, self.config.height);
         self.surface.configure(&self.device, &self.config);
         self.camera_projection.resize(size.0 as u32, size.1 as u32);
     }
 
+    // every texture rendered into before the tone mapping, the surface is left as is
+    fn resize_targets(&mut self, width: u32, height: u32) {
+        let config = wgpu::SurfaceConfiguration {
+            width,
+            height,
+            ..self.config.clone()
+        };
+        self.depth_texture =
+            texture::Texture::create_depth_texture(&self.device, &config, self.sample_count);
+        self.multisampled_texture = (self.sample_count > 1).then(|| {
+            texture::Texture::create_multisampled_texture(
+                &self.device,
+                &config,
+                hdr::HDR_FORMAT,
+                self.sample_count,
+            )
+        });
+        self.oit.resize(&self.device, &config);
+        self.hdr.resize(&self.device, width, height);
+        self.bloom.resize(&self.device, &self.hdr.target);
+        self.post_process.resize(&self.device, width, height);
+    }
+
     fn update_surface(&mut self, size: Option<(i32, i32)>) {
         let target = unsafe { wgpu::SurfaceTargetUnsafe::from_window(&self.window) }.unwrap();
         self.surface = unsafe { self.instance.create_surface_unsafe(target) }.unwrap();
@@ -258,6 +1512,167 @@ impl<'a> State<'a> {
     }
 }
 
+// lit and meshed, chunk meshes being empty for the chunks without any visible face
+fn generate_world(
+    device: &wgpu::Device,
+    seed: u32,
+) -> (world::World, HashMap<world::ChunkPos, mesher::ChunkMesh>) {
+    // next to the test instances, which cover positive x and z
+    let mut world = world::World::generate(
+        seed,
+        world::ChunkPos::new(-6, 0),
+        world::ChunkPos::new(-1, 4),
+    );
+    light::light_world(&mut world);
+    let chunk_meshes = world
+        .chunks
+        .keys()
+        .filter_map(|&chunk_pos| {
+            let mesh = mesher::build_chunk_mesh(&world, chunk_pos);
+            mesher::ChunkMesh::new(device, &mesh, "Chunk").map(|mesh| (chunk_pos, mesh))
+        })
+        .collect();
+    (world, chunk_meshes)
+}
+
+// every pipeline drawing shaders/shader.wgsl, built again by `pipeline reload`
+struct ScenePipelines {
+    render: wgpu::RenderPipeline,
+    cutout: wgpu::RenderPipeline,
+    culled: wgpu::RenderPipeline,
+    translucent: wgpu::RenderPipeline,
+    oit: wgpu::RenderPipeline,
+    voxel: wgpu::RenderPipeline,
+}
+
+impl ScenePipelines {
+    // the pipelines but the debug views, see debug_view::DebugState, with their labels
+    fn builders<'a>(
+        device: &'a wgpu::Device,
+        layouts: &[&'a wgpu::BindGroupLayout],
+        sample_count: u32,
+    ) -> [(&'static str, pipeline::Builder<'a>); 6] {
+        let render = scene_pipeline_builder(device, layouts, sample_count);
+        // two sided, e.g. for leaves
+        let mut cutout = scene_pipeline_builder(device, layouts, sample_count);
+        cutout.add_define("CUTOUT", "");
+        // smooth edges from the coverage instead of discarding, when multisampled
+        if sample_count > 1 {
+            cutout.add_define("ALPHA_TO_COVERAGE", "");
+        }
+        cutout.set_alpha_to_coverage(true);
+        cutout.set_cull_mode(None);
+        // instances culled by a frozen frustum, shown instead of skipped
+        let mut culled = scene_pipeline_builder(device, layouts, sample_count);
+        culled.add_define("CULLED", "");
+        let mut translucent = scene_pipeline_builder(device, layouts, sample_count);
+        translucent.set_blend_preset(pipeline::BlendPreset::Alpha);
+        translucent.set_depth_write(false);
+        let mut oit = scene_pipeline_builder(device, layouts, sample_count);
+        transparency::configure_accumulate_pipeline(&mut oit);
+        let voxel = voxel_pipeline_builder(device, layouts, sample_count);
+        [
+            ("Render Pipeline", render),
+            ("Cutout Pipeline", cutout),
+            ("Culled Pipeline", culled),
+            ("Translucent Pipeline", translucent),
+            ("OIT Accumulate Pipeline", oit),
+            ("Voxel Pipeline", voxel),
+        ]
+    }
+
+    // returns the first shader error instead of panicking
+    fn new(
+        device: &wgpu::Device,
+        layouts: &[&wgpu::BindGroupLayout],
+        sample_count: u32,
+    ) -> Result<Self, String> {
+        let [render, cutout, culled, translucent, oit, voxel] =
+            Self::builders(device, layouts, sample_count)
+                .map(|(label, mut builder)| builder.try_build_pipeline(label));
+        Ok(Self {
+            render: render?,
+            cutout: cutout?,
+            culled: culled?,
+            translucent: translucent?,
+            oit: oit?,
+            voxel: voxel?,
+        })
+    }
+
+    // every permutation of shaders/shader.wgsl the scene is drawn with, the scene bind group
+    // layouts being reflected from all of them so that bindings behind a define get an entry
+    fn reflect(device: &wgpu::Device, sample_count: u32) -> Result<Vec<ShaderReflection>, String> {
+        let mut builders = Self::builders(device, &[], sample_count)
+            .into_iter()
+            .map(|(_, builder)| builder)
+            .collect::<Vec<_>>();
+        // the debug views are built once selected, with layouts reflected beforehand
+        for view in debug_view::DebugView::all() {
+            let mut instanced = scene_pipeline_builder(device, &[], sample_count);
+            debug_view::configure_pipeline(&mut instanced, view);
+            let mut voxel = voxel_pipeline_builder(device, &[], sample_count);
+            debug_view::configure_pipeline(&mut voxel, view);
+            builders.extend([instanced, voxel]);
+        }
+        builders.iter().map(pipeline::Builder::reflect).collect()
+    }
+}
+
+// camera, texture and environment layouts, groups 0 to 2 of shaders/shader.wgsl
+fn create_scene_layouts(
+    device: &wgpu::Device,
+    reflections: &[ShaderReflection],
+) -> Result<[wgpu::BindGroupLayout; 3], String> {
+    let layout = |group: u32, label: &str| {
+        let mut builder = bind_group_layout::Builder::new(device);
+        builder.try_add_reflected_entries(reflections, group)?;
+        Ok::<_, String>(builder.build(label))
+    };
+    Ok([
+        layout(0, "Camera Bind Group Layout")?,
+        layout(1, "Texture Bind Group Layout")?,
+        layout(2, "Environment Bind Group Layout")?,
+    ])
+}
+
+// shared by the opaque, cutout and translucent pipelines
+fn scene_pipeline_builder<'a>(
+    device: &'a wgpu::Device,
+    layouts: &[&'a wgpu::BindGroupLayout],
+    sample_count: u32,
+) -> pipeline::Builder<'a> {
+    let mut builder = pipeline::Builder::new(device);
+    builder.add_vertex_buffer_layout(model::ModelVertex::desc());
+    builder.add_vertex_buffer_layout(instance::InstanceRaw::desc());
+    builder.set_shader_module("shaders/shader.wgsl", "vertex_main", "fragment_main");
+    builder.set_pixel_format(hdr::HDR_FORMAT);
+    builder.set_front_face(wgpu::FrontFace::Cw);
+    builder.set_sample_count(sample_count);
+    for layout in layouts {
+        builder.add_bind_group_layout(layout);
+    }
+    builder
+}
+
+// chunk meshes, in world space
+fn voxel_pipeline_builder<'a>(
+    device: &'a wgpu::Device,
+    layouts: &[&'a wgpu::BindGroupLayout],
+    sample_count: u32,
+) -> pipeline::Builder<'a> {
+    let mut builder = pipeline::Builder::new(device);
+    builder.add_vertex_buffer_layout(mesher::VoxelVertex::desc());
+    builder.set_shader_module("shaders/shader.wgsl", "voxel_vertex_main", "fragment_main");
+    builder.set_pixel_format(hdr::HDR_FORMAT);
+    builder.set_front_face(wgpu::FrontFace::Ccw);
+    builder.set_sample_count(sample_count);
+    for layout in layouts {
+        builder.add_bind_group_layout(layout);
+    }
+    builder
+}
+
 async fn run() {
     let mut glfw = glfw::init(fail_on_errors!()).unwrap();
     let (mut window, events) = glfw
@@ -268,22 +1683,50 @@ async fn run() {
     window.make_current();
 
     let mut state = State::new(&mut window).await;
+    state.update_far_plane();
+    if !state.gpu_timer.is_enabled() {
+        state
+            .console
+            .print("TIMESTAMP_QUERY is not supported, GPU timings are disabled");
+    }
+    if pipeline::supported_polygon_mode(&state.device, wgpu::PolygonMode::Line)
+        != wgpu::PolygonMode::Line
+    {
+        state
+            .console
+            .print("POLYGON_MODE_LINE is not supported, the wireframe view is drawn filled");
+    }
+    let startup_script = std::path::Path::new(STARTUP_SCRIPT);
+    if startup_script.exists() {
+        state.run_script(startup_script);
+    }
     let mut current_frame: SystemTime = SystemTime::now();
     let mut last_frame: SystemTime;
     let mut delta_time: Duration;
+    let mut frame_time: Duration;
 
     while !state.window.should_close() {
         last_frame = current_frame;
         current_frame = SystemTime::now();
-        delta_time = current_frame.duration_since(last_frame).unwrap();
+        frame_time = current_frame.duration_since(last_frame).unwrap_or_default();
+        // simulated time only advances by whole frames while capturing
+        delta_time = match &state.capture {
+            Some(capture) => capture.time_step(),
+            None => frame_time,
+        };
 
+        let update_start = Instant::now();
+        state.update(delta_time);
         state
-            .camera_controller
-            .update_camera(&mut state.camera, delta_time);
+            .metrics
+            .add_phase(Phase::Update, update_start.elapsed());
 
         glfw.poll_events();
         for (_, event) in glfw::flush_messages(&events) {
-            if state.camera_controller.process_events(&event) {
+            if state.console.handle_event(&event)
+                || state.debug_ui.handle_event(state.window, &event)
+                || state.camera_controller.process_events(&event)
+            {
                 continue;
             }
 
@@ -292,6 +1735,176 @@ async fn run() {
                     println!("Escape pressed: closing window...");
                     state.window.set_should_close(true)
                 }
+                glfw::WindowEvent::Key(Key::T, _, Action::Press, _) => {
+                    state.hdr.tone_mapping = state.hdr.tone_mapping.next();
+                    state
+                        .console
+                        .print(format!("Tone mapping: {:?}", state.hdr.tone_mapping));
+                }
+                glfw::WindowEvent::Key(Key::X, _, Action::Press, _) => {
+                    state.hdr.auto_exposure = !state.hdr.auto_exposure;
+                    state
+                        .console
+                        .print(format!("Auto exposure: {}", state.hdr.auto_exposure));
+                }
+                glfw::WindowEvent::Key(Key::K, _, Action::Press, _) => {
+                    state.sky.next_mode();
+                    state.console.print(format!("Sky: {:?}", state.sky.mode));
+                }
+                glfw::WindowEvent::Key(Key::F, _, Action::Press, _) => {
+                    state.fog.mode = state.fog.mode.next();
+                    state.update_far_plane();
+                    state.console.print(format!(
+                        "Fog: {:?}, far plane at {}",
+                        state.fog.mode,
+                        state.camera_projection.zfar()
+                    ));
+                }
+                glfw::WindowEvent::Key(Key::O, _, Action::Press, _) => {
+                    state.transparency_mode = state.transparency_mode.next();
+                    state
+                        .console
+                        .print(format!("Transparency: {:?}", state.transparency_mode));
+                }
+                glfw::WindowEvent::Key(Key::P, _, Action::Press, _) => {
+                    state.time_of_day.toggle_pause();
+                    state
+                        .console
+                        .print(format!("Time paused: {}", state.time_of_day.paused));
+                }
+                glfw::WindowEvent::Key(
+                    key @ (Key::LeftBracket | Key::RightBracket),
+                    _,
+                    Action::Press | Action::Repeat,
+                    _,
+                ) => {
+                    let hour = 1.0 / 24.0;
+                    state
+                        .time_of_day
+                        .scrub(if key == Key::LeftBracket { -hour } else { hour });
+                    let (hours, minutes) = state.time_of_day.clock();
+                    state.console.print(format!(
+                        "Day {} {:02}:{:02}",
+                        state.time_of_day.day(),
+                        hours,
+                        minutes
+                    ));
+                }
+                glfw::WindowEvent::Key(Key::L, _, Action::Press, _) => {
+                    // toggles a light a few blocks in front of the camera
+                    let target = state.camera.position + state.camera.front() * 4.0;
+                    let (x, y, z) = (
+                        target.x.floor() as i32,
+                        target.y.floor() as i32,
+                        target.z.floor() as i32,
+                    );
+                    let block = match state.world.get_block(x, y, z) {
+                        world::Block::Glowstone => world::Block::Air,
+                        _ => world::Block::Glowstone,
+                    };
+                    state.set_block(x, y, z, block);
+                    state
+                        .console
+                        .print(format!("{:?} at ({}, {}, {})", block, x, y, z));
+                }
+                glfw::WindowEvent::Key(Key::F1, _, Action::Press, _) => {
+                    state.toggle_debug_ui();
+                }
+                glfw::WindowEvent::Key(Key::F2, _, Action::Press, modifiers) => {
+                    state.screenshot_scale = Some(if modifiers.contains(glfw::Modifiers::Shift) {
+                        SUPERSAMPLED_SCREENSHOT_SCALE
+                    } else {
+                        1
+                    });
+                }
+                glfw::WindowEvent::Key(Key::F3, _, Action::Press, _) => {
+                    state.frame_graph.enabled = !state.frame_graph.enabled;
+                }
+                glfw::WindowEvent::Key(Key::F4, _, Action::Press, _) => {
+                    state.toggle_metrics_log();
+                }
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, modifiers) => {
+                    state.toggle_capture(if modifiers.contains(glfw::Modifiers::Shift) {
+                        capture::CaptureFormat::Y4m
+                    } else {
+                        capture::CaptureFormat::Png
+                    });
+                }
+                glfw::WindowEvent::Key(Key::C, _, Action::Press, modifiers) => {
+                    if modifiers.contains(glfw::Modifiers::Shift) {
+                        state.camera_path.clear();
+                    } else {
+                        state.camera_path.add_keyframe(&state.camera);
+                    }
+                    state.save_camera_path();
+                }
+                glfw::WindowEvent::Key(Key::V, _, Action::Press, modifiers) => {
+                    if modifiers.contains(glfw::Modifiers::Shift) {
+                        state.camera_path.easing = state.camera_path.easing.next();
+                        state.console.print(format!(
+                            "Camera path easing: {:?}",
+                            state.camera_path.easing
+                        ));
+                    } else if state.camera_path.is_playing() {
+                        state.camera_path.stop();
+                    } else if !state.camera_path.play() {
+                        state
+                            .console
+                            .print("Camera path needs at least two keyframes at different times");
+                    }
+                }
+                glfw::WindowEvent::Key(Key::F5, _, Action::Press, modifiers) => {
+                    if modifiers.contains(glfw::Modifiers::Shift) {
+                        state.tint_culled = !state.tint_culled;
+                        state
+                            .console
+                            .print(format!("Show culled instances: {}", state.tint_culled));
+                    } else {
+                        state.toggle_frozen_frustum();
+                        state.console.print(format!(
+                            "Frustum frozen: {}",
+                            state.frozen_frustum.is_some()
+                        ));
+                    }
+                }
+                glfw::WindowEvent::Key(Key::F6, _, Action::Press, modifiers) => {
+                    state.debug.view = if modifiers.contains(glfw::Modifiers::Shift) {
+                        debug_view::DebugView::Lit
+                    } else {
+                        state.debug.view.next()
+                    };
+                    state
+                        .console
+                        .print(format!("Debug view: {:?}", state.debug.view));
+                }
+                glfw::WindowEvent::Key(Key::G, _, Action::Press, _) => {
+                    state.debug_draw.enabled = !state.debug_draw.enabled;
+                    state
+                        .console
+                        .print(format!("Debug draw: {}", state.debug_draw.enabled));
+                }
+                glfw::WindowEvent::Key(Key::B, _, Action::Press, _) => {
+                    state.bloom.enabled = !state.bloom.enabled;
+                    state
+                        .console
+                        .print(format!("Bloom: {}", state.bloom.enabled));
+                }
+                glfw::WindowEvent::Key(Key::Equal, _, Action::Press | Action::Repeat, _) => {
+                    state.hdr.exposure *= 1.25;
+                }
+                glfw::WindowEvent::Key(Key::Minus, _, Action::Press | Action::Repeat, _) => {
+                    state.hdr.exposure /= 1.25;
+                }
+                glfw::WindowEvent::Key(key, _, Action::Press, _)
+                    if (Key::Num1 as i32..=Key::Num9 as i32).contains(&(key as i32)) =>
+                {
+                    let index = (key as i32 - Key::Num1 as i32) as usize;
+                    if let Some(effect) = state.post_process.toggle(index) {
+                        state
+                            .console
+                            .print(format!("{}: {}", effect.name, effect.enabled));
+                    }
+                }
                 glfw::WindowEvent::FramebufferSize(witdh, height) => {
                     state.update_surface(Some((witdh, height)));
                 }
@@ -303,6 +1916,9 @@ async fn run() {
                 }
             }
         }
+        while let Some(line) = state.console.take_submitted() {
+            state.run_command(&line);
+        }
 
         match state.render() {
             Ok(_) => {}
@@ -312,6 +1928,14 @@ async fn run() {
             Err(e) => eprintln!("Error: {}", e),
         }
         state.window.swap_buffers();
+        let gpu_timings = state.gpu_timer.take_timings();
+        if let Err(e) = state.metrics.end_frame(frame_time, &gpu_timings) {
+            state.console.print(format!("metrics log stopped: {}", e));
+        }
+    }
+    // the window is closed, the console cannot show it anymore
+    if let Err(e) = state.metrics.stop_csv() {
+        eprintln!("{}", e);
     }
 }
 
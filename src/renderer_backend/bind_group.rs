@@ -1,6 +1,11 @@
+use super::reflection::ShaderReflection;
+
 pub struct Builder<'a> {
     entries: Vec<wgpu::BindGroupEntry<'a>>,
     layout: Option<&'a wgpu::BindGroupLayout>,
+    // the shader permutations the layout was reflected from, see `bind`
+    reflections: &'a [ShaderReflection],
+    group: u32,
     device: &'a wgpu::Device,
 }
 
@@ -9,6 +14,8 @@ impl<'a> Builder<'a> {
         Builder {
             entries: Vec::new(),
             layout: None,
+            reflections: &[],
+            group: 0,
             device,
         }
     }
@@ -21,21 +28,87 @@ impl<'a> Builder<'a> {
         self.layout = Some(layout);
     }
 
+    // for a layout made by bind_group_layout::Builder::add_reflected_entries, with the same
+    // reflections and group
+    pub fn set_reflected_layout(
+        &mut self,
+        layout: &'a wgpu::BindGroupLayout,
+        reflections: &'a [ShaderReflection],
+        group: u32,
+    ) {
+        self.layout = Some(layout);
+        self.reflections = reflections;
+        self.group = group;
+    }
+
+    // the add_* methods bind after the highest binding so far, in the order of the layout
+    fn next_binding(&self) -> u32 {
+        self.entries
+            .iter()
+            .map(|entry| entry.binding + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // to the binding the reflected shader declares the variable `name` at
+    pub fn bind(&mut self, name: &str, resource: wgpu::BindingResource<'a>) {
+        let binding = self
+            .reflections
+            .iter()
+            .find_map(|reflection| reflection.binding_index(self.group, name))
+            .unwrap_or_else(|| panic!("no `{}` in group {} of the shader", name, self.group));
+        self.entries
+            .push(wgpu::BindGroupEntry { binding, resource });
+    }
+
     pub fn add_buffer(&mut self, buffer: &'a wgpu::Buffer) {
         self.entries.push(wgpu::BindGroupEntry {
-            binding: self.entries.len() as u32,
+            binding: self.next_binding(),
             resource: buffer.as_entire_binding(),
         });
     }
 
+    // binds `size` bytes, as dynamic offsets are applied on top of the binding
+    #[allow(dead_code)]
+    pub fn add_buffer_range(
+        &mut self,
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        size: wgpu::BufferSize,
+    ) {
+        self.entries.push(wgpu::BindGroupEntry {
+            binding: self.next_binding(),
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer,
+                offset,
+                size: Some(size),
+            }),
+        });
+    }
+
+    pub fn add_texture_view(&mut self, view: &'a wgpu::TextureView) {
+        self.entries.push(wgpu::BindGroupEntry {
+            binding: self.next_binding(),
+            resource: wgpu::BindingResource::TextureView(view),
+        });
+    }
+
+    #[allow(dead_code)]
+    pub fn add_sampler(&mut self, sampler: &'a wgpu::Sampler) {
+        self.entries.push(wgpu::BindGroupEntry {
+            binding: self.next_binding(),
+            resource: wgpu::BindingResource::Sampler(sampler),
+        });
+    }
+
     pub fn add_texture(&mut self, view: &'a wgpu::TextureView, sampler: &'a wgpu::Sampler) {
         self.entries.push(wgpu::BindGroupEntry {
-            binding: self.entries.len() as u32,
+            binding: self.next_binding(),
             resource: wgpu::BindingResource::TextureView(view),
         });
 
         self.entries.push(wgpu::BindGroupEntry {
-            binding: self.entries.len() as u32,
+            binding: self.next_binding(),
             resource: wgpu::BindingResource::Sampler(sampler),
         });
     }
@@ -1,3 +1,5 @@
+use super::reflection::ShaderReflection;
+
 pub struct Builder<'a> {
     entries: Vec<wgpu::BindGroupLayoutEntry>,
     device: &'a wgpu::Device,
@@ -28,6 +30,110 @@ impl<'a> Builder<'a> {
         });
     }
 
+    #[allow(dead_code)]
+    pub fn add_dynamic_buffer(&mut self, shader_stage: wgpu::ShaderStages) {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.entries.len() as u32,
+            visibility: shader_stage,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+    }
+
+    #[allow(dead_code)]
+    pub fn add_storage_buffer(&mut self, shader_stage: wgpu::ShaderStages, read_only: bool) {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.entries.len() as u32,
+            visibility: shader_stage,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+    }
+
+    #[allow(dead_code)]
+    pub fn add_storage_texture(
+        &mut self,
+        shader_stage: wgpu::ShaderStages,
+        format: wgpu::TextureFormat,
+        access: wgpu::StorageTextureAccess,
+        view_dimension: wgpu::TextureViewDimension,
+    ) {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.entries.len() as u32,
+            visibility: shader_stage,
+            ty: wgpu::BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension,
+            },
+            count: None,
+        });
+    }
+
+    // a lone texture binding, without the sampler add_texture pairs it with
+    pub fn add_texture_view(
+        &mut self,
+        shader_stage: wgpu::ShaderStages,
+        sample_type: wgpu::TextureSampleType,
+        view_dimension: wgpu::TextureViewDimension,
+    ) {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.entries.len() as u32,
+            visibility: shader_stage,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        });
+    }
+
+    #[allow(dead_code)]
+    pub fn add_texture_array(&mut self, shader_stage: wgpu::ShaderStages) {
+        self.add_texture_view(
+            shader_stage,
+            wgpu::TextureSampleType::Float { filterable: true },
+            wgpu::TextureViewDimension::D2Array,
+        );
+    }
+
+    #[allow(dead_code)]
+    pub fn add_depth_texture(&mut self, shader_stage: wgpu::ShaderStages) {
+        self.add_texture_view(
+            shader_stage,
+            wgpu::TextureSampleType::Depth,
+            wgpu::TextureViewDimension::D2,
+        );
+    }
+
+    pub fn add_sampler(&mut self, shader_stage: wgpu::ShaderStages) {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.entries.len() as u32,
+            visibility: shader_stage,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        });
+    }
+
+    #[allow(dead_code)]
+    pub fn add_comparison_sampler(&mut self, shader_stage: wgpu::ShaderStages) {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.entries.len() as u32,
+            visibility: shader_stage,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+            count: None,
+        });
+    }
+
     pub fn add_texture(&mut self) {
         self.entries.push(wgpu::BindGroupLayoutEntry {
             binding: self.entries.len() as u32,
@@ -48,6 +154,33 @@ impl<'a> Builder<'a> {
         });
     }
 
+    // cube texture + filtering sampler, e.g. for skyboxes
+    pub fn add_cube_texture(&mut self) {
+        self.add_texture_view(
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::TextureSampleType::Float { filterable: true },
+            wgpu::TextureViewDimension::Cube,
+        );
+        self.add_sampler(wgpu::ShaderStages::FRAGMENT);
+    }
+
+    // `reflections` are the permutations of the shader the layout is used with, every
+    // binding any of them declares getting an entry
+    pub fn add_reflected_entries(&mut self, reflections: &[ShaderReflection], group: u32) {
+        self.try_add_reflected_entries(reflections, group)
+            .unwrap_or_else(|message| panic!("{}", message));
+    }
+
+    pub fn try_add_reflected_entries(
+        &mut self,
+        reflections: &[ShaderReflection],
+        group: u32,
+    ) -> Result<(), String> {
+        let entries = ShaderReflection::merged_bind_group_layout_entries(reflections, group)?;
+        self.entries.extend(entries);
+        Ok(())
+    }
+
     pub fn build(&mut self, label: &str) -> wgpu::BindGroupLayout {
         let layout = self
             .device
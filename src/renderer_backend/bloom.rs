@@ -0,0 +1,285 @@
+use super::{bind_group, bind_group_layout, hdr::HDR_FORMAT, mipmap, pipeline, texture};
+
+const MAX_MIP_COUNT: u32 = 6;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomUniform {
+    threshold: f32,
+    knee: f32,
+    filter_radius: f32,
+    _padding: f32,
+}
+
+// the bright parts of the HDR target are downsampled into a half resolution mip chain,
+// upsampled back with a tent filter and added to the target before tone mapping
+pub struct BloomPipeline {
+    pub enabled: bool,
+    // HDR value above which pixels bloom, with a soft transition of `knee` on each side
+    pub threshold: f32,
+    pub knee: f32,
+    pub intensity: f32,
+    // tent filter radius in uv units
+    pub filter_radius: f32,
+    params_buffer: wgpu::Buffer,
+    texture_layout: wgpu::BindGroupLayout,
+    params_bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    mip_views: Vec<wgpu::TextureView>,
+    // the target first, then every mip of the chain
+    source_bind_groups: Vec<wgpu::BindGroup>,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+impl BloomPipeline {
+    pub fn new(device: &wgpu::Device, target: &texture::Texture) -> Self {
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Bloom Params Buffer"),
+            size: std::mem::size_of::<BloomUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let texture_layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_texture();
+            builder.build("Bloom Texture Layout")
+        };
+        let params_layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_buffer(wgpu::ShaderStages::FRAGMENT);
+            builder.build("Bloom Params Layout")
+        };
+        let params_bind_group = {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&params_layout);
+            builder.add_buffer(&params_buffer);
+            builder.build("Bloom Params Bind Group")
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Bloom Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mut builder = pipeline::Builder::new(device);
+        let mut build_pipeline = |entry: &str, blend: Option<wgpu::BlendState>, label: &str| {
+            builder.set_fullscreen_shader("shaders/bloom.wgsl", entry);
+            builder.set_pixel_format(HDR_FORMAT);
+            builder.set_blend(blend);
+            builder.add_bind_group_layout(&texture_layout);
+            builder.add_bind_group_layout(&params_layout);
+            builder.build_pipeline(label)
+        };
+        let prefilter_pipeline = build_pipeline(
+            "prefilter_main",
+            Some(wgpu::BlendState::REPLACE),
+            "Bloom Prefilter Pipeline",
+        );
+        let downsample_pipeline = build_pipeline(
+            "downsample_main",
+            Some(wgpu::BlendState::REPLACE),
+            "Bloom Downsample Pipeline",
+        );
+        let upsample_pipeline = build_pipeline(
+            "upsample_main",
+            Some(pipeline::BlendPreset::Additive.state()),
+            "Bloom Upsample Pipeline",
+        );
+        // target + bloom * intensity, the intensity being the blend constant
+        let composite_pipeline = build_pipeline(
+            "composite_main",
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Constant,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            "Bloom Composite Pipeline",
+        );
+
+        let (mip_views, source_bind_groups) =
+            Self::create_mip_chain(device, &texture_layout, &sampler, target);
+
+        Self {
+            enabled: true,
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.15,
+            filter_radius: 0.005,
+            params_buffer,
+            texture_layout,
+            params_bind_group,
+            sampler,
+            mip_views,
+            source_bind_groups,
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+        }
+    }
+
+    fn create_mip_chain(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        target: &texture::Texture,
+    ) -> (Vec<wgpu::TextureView>, Vec<wgpu::BindGroup>) {
+        let size = target.texture.size();
+        let width = (size.width / 2).max(1);
+        let height = (size.height / 2).max(1);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Bloom Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mipmap::mip_level_count(width, height).min(MAX_MIP_COUNT),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let mip_views = (0..texture.mip_level_count())
+            .map(|mip_level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Bloom Mip View"),
+                    base_mip_level: mip_level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let source_bind_groups = std::iter::once(&target.view)
+            .chain(mip_views.iter())
+            .map(|view| {
+                let mut builder = bind_group::Builder::new(device);
+                builder.set_layout(layout);
+                builder.add_texture(view, sampler);
+                builder.build("Bloom Source Bind Group")
+            })
+            .collect();
+
+        (mip_views, source_bind_groups)
+    }
+
+    // must be called whenever the target is recreated
+    pub fn resize(&mut self, device: &wgpu::Device, target: &texture::Texture) {
+        (self.mip_views, self.source_bind_groups) =
+            Self::create_mip_chain(device, &self.texture_layout, &self.sampler, target);
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let bloom_uniform = BloomUniform {
+            threshold: self.threshold,
+            knee: self.knee.max(0.0),
+            filter_radius: self.filter_radius,
+            _padding: 0.0,
+        };
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[bloom_uniform]),
+        );
+    }
+
+    fn draw(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        pipeline: &wgpu::RenderPipeline,
+        source: &wgpu::BindGroup,
+        output: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, source, &[]);
+        render_pass.set_bind_group(1, &self.params_bind_group, &[]);
+        let intensity = self.intensity as f64;
+        render_pass.set_blend_constant(wgpu::Color {
+            r: intensity,
+            g: intensity,
+            b: intensity,
+            a: intensity,
+        });
+        render_pass.draw(0..3, 0..1);
+    }
+
+    // `target` must be the texture given to new/resize
+    pub fn apply(&self, command_encoder: &mut wgpu::CommandEncoder, target: &texture::Texture) {
+        if !self.enabled {
+            return;
+        }
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+
+        self.draw(
+            command_encoder,
+            "Bloom Prefilter Pass",
+            &self.prefilter_pipeline,
+            &self.source_bind_groups[0],
+            &self.mip_views[0],
+            clear,
+        );
+        for mip in 1..self.mip_views.len() {
+            self.draw(
+                command_encoder,
+                "Bloom Downsample Pass",
+                &self.downsample_pipeline,
+                &self.source_bind_groups[mip],
+                &self.mip_views[mip],
+                clear,
+            );
+        }
+        for mip in (0..self.mip_views.len() - 1).rev() {
+            self.draw(
+                command_encoder,
+                "Bloom Upsample Pass",
+                &self.upsample_pipeline,
+                &self.source_bind_groups[mip + 2],
+                &self.mip_views[mip],
+                wgpu::LoadOp::Load,
+            );
+        }
+        self.draw(
+            command_encoder,
+            "Bloom Composite Pass",
+            &self.composite_pipeline,
+            &self.source_bind_groups[1],
+            &target.view,
+            wgpu::LoadOp::Load,
+        );
+    }
+}
@@ -38,6 +38,24 @@ impl Camera {
         Matrix4::look_to_rh(self.position, self.front, Vector3::unit_y())
     }
 
+    pub fn front(&self) -> Vector3<f32> {
+        self.front
+    }
+
+    pub fn yaw(&self) -> Rad<f32> {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> Rad<f32> {
+        self.pitch
+    }
+
+    pub fn set_rotation(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.update_directions();
+    }
+
     pub fn update_directions(&mut self) {
         let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
         let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
@@ -68,6 +86,31 @@ impl Projection {
         self.aspect = width as f32 / height as f32;
     }
 
+    pub fn fovy(&self) -> Rad<f32> {
+        self.fovy
+    }
+
+    pub fn set_fovy<F: Into<Rad<f32>>>(&mut self, fovy: F) {
+        let fovy: Rad<f32> = fovy.into();
+        self.fovy = Rad(fovy.0.clamp(0.01, std::f32::consts::PI - 0.01));
+    }
+
+    pub fn znear(&self) -> f32 {
+        self.znear
+    }
+
+    pub fn set_znear(&mut self, znear: f32) {
+        self.znear = znear.clamp(0.001, self.zfar - 1.0);
+    }
+
+    pub fn zfar(&self) -> f32 {
+        self.zfar
+    }
+
+    pub fn set_zfar(&mut self, zfar: f32) {
+        self.zfar = zfar.max(self.znear + 1.0);
+    }
+
     pub fn calc_matrix(&self) -> Matrix4<f32> {
         perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
@@ -88,9 +131,17 @@ impl Plane {
     pub fn get_signed_distance(&self, vector: Vector3<f32>) -> f32 {
         self.normal.dot(vector) - self.distance
     }
+
+    // the point shared by three planes, none of them parallel
+    fn intersection(a: &Plane, b: &Plane, c: &Plane) -> Vector3<f32> {
+        let denominator = a.normal.dot(b.normal.cross(c.normal));
+        (b.normal.cross(c.normal) * a.distance
+            + c.normal.cross(a.normal) * b.distance
+            + a.normal.cross(b.normal) * c.distance)
+            / denominator
+    }
 }
 
-const CENTER_OFFSET: Vector3<f32> = Vector3::new(0.5, 0.5, 0.5);
 pub struct Frustum {
     near_plane: Plane,
     far_plane: Plane,
@@ -153,6 +204,7 @@ impl Frustum {
         }
     }
 
+    #[allow(dead_code)]
     pub fn is_inside(&self, vector: Vector3<f32>) -> bool {
         let mut inside = true;
         let planes = vec![
@@ -172,24 +224,39 @@ impl Frustum {
         inside
     }
 
-    pub fn is_inside_instance(&self, instance: &Instance) -> bool {
-        let mut inside = true;
-        let planes = vec![
+    pub fn is_inside_sphere(&self, center: Vector3<f32>, radius: f32) -> bool {
+        [
             &self.near_plane,
             &self.far_plane,
             &self.top_plane,
             &self.bottom_plane,
             &self.right_plane,
             &self.left_plane,
-        ];
-        for plane in planes {
-            let truc = plane.get_signed_distance(instance.position + CENTER_OFFSET);
-            if truc < -instance.scale * f32::sqrt(3.0) / 2.0 {
-                inside = false;
-                break;
+        ]
+        .iter()
+        .all(|plane| plane.get_signed_distance(center) >= -radius)
+    }
+
+    pub fn is_inside_instance(&self, instance: &Instance) -> bool {
+        let (center, radius) = instance.bounding_sphere();
+        self.is_inside_sphere(center, radius)
+    }
+
+    // near face then far face, each bottom left, bottom right, top right and top left
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        let mut corners = [Vector3::zero(); 8];
+        for (i, depth_plane) in [&self.near_plane, &self.far_plane].iter().enumerate() {
+            let sides = [
+                (&self.bottom_plane, &self.left_plane),
+                (&self.bottom_plane, &self.right_plane),
+                (&self.top_plane, &self.right_plane),
+                (&self.top_plane, &self.left_plane),
+            ];
+            for (j, (vertical, horizontal)) in sides.iter().enumerate() {
+                corners[i * 4 + j] = Plane::intersection(depth_plane, vertical, horizontal);
             }
         }
-        inside
+        corners
     }
 }
 
@@ -198,6 +265,7 @@ impl Frustum {
 pub struct CameraUniform {
     view_position: [f32; 4],
     view_proj: [[f32; 4]; 4],
+    inv_view_proj: [[f32; 4]; 4],
 }
 
 impl CameraUniform {
@@ -206,12 +274,15 @@ impl CameraUniform {
         Self {
             view_position: [0.0, 0.0, 0.0, 1.0],
             view_proj: cgmath::Matrix4::identity().into(),
+            inv_view_proj: cgmath::Matrix4::identity().into(),
         }
     }
 
     pub fn update_view_proj(&mut self, camera: &Camera, projection: &Projection) {
         self.view_position = camera.position.to_homogeneous().into();
-        self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
+        let view_proj = projection.calc_matrix() * camera.calc_matrix();
+        self.view_proj = view_proj.into();
+        self.inv_view_proj = view_proj.invert().unwrap_or(Matrix4::identity()).into();
     }
 }
 
@@ -249,6 +320,19 @@ impl CameraController {
         }
     }
 
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    // for the cursor moving while its events went elsewhere, not to turn the camera
+    pub fn reset_cursor(&mut self, cursor_pos: (f64, f64)) {
+        self.old_cursor_pos = cursor_pos;
+    }
+
     pub fn process_events(&mut self, event: &WindowEvent) -> bool {
         match event {
             WindowEvent::Key(key, _, action, _) => self.process_keyboard(*key, *action),
@@ -0,0 +1,326 @@
+use std::{f32::consts::PI, fs, io, path::Path, time::Duration};
+
+use cgmath::{Point3, Rad};
+
+use super::camera::Camera;
+
+// time between a new keyframe and the previous one
+pub const KEYFRAME_INTERVAL: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    // slow start and end of the whole path, constant in between
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn next(self) -> Self {
+        match self {
+            Easing::Linear => Easing::EaseInOut,
+            Easing::EaseInOut => Easing::Linear,
+        }
+    }
+
+    // maps [0, 1] onto itself
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    // seconds from the start of the path
+    pub time: f32,
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+}
+
+// keyframes played back along a Catmull-Rom spline going through all of them
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    pub easing: Easing,
+    // playback position in seconds, None when not playing
+    elapsed: Option<f32>,
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self {
+            keyframes: Vec::new(),
+            easing: Easing::EaseInOut,
+            elapsed: None,
+        }
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn add_keyframe(&mut self, camera: &Camera) {
+        let time = self
+            .keyframes
+            .last()
+            .map_or(0.0, |keyframe| keyframe.time + KEYFRAME_INTERVAL);
+        self.keyframes.push(Keyframe {
+            time,
+            position: camera.position,
+            yaw: camera.yaw(),
+            pitch: camera.pitch(),
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+        self.elapsed = None;
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.elapsed.is_some()
+    }
+
+    // needs at least two keyframes at different times
+    pub fn play(&mut self) -> bool {
+        self.elapsed = (self.keyframes.len() >= 2 && self.duration() > 0.0).then_some(0.0);
+        self.is_playing()
+    }
+
+    pub fn stop(&mut self) {
+        self.elapsed = None;
+    }
+
+    // moves the camera along the path, stopping at its end
+    pub fn update(&mut self, camera: &mut Camera, delta_time: Duration) {
+        let Some(elapsed) = self.elapsed else {
+            return;
+        };
+        let elapsed = elapsed + delta_time.as_secs_f32();
+        let duration = self.duration();
+        let eased = self.easing.apply((elapsed / duration).min(1.0)) * duration;
+        if let Some((position, yaw, pitch)) = self.sample(eased) {
+            camera.position = position;
+            camera.set_rotation(yaw, pitch);
+        }
+        self.elapsed = (elapsed < duration).then_some(elapsed);
+    }
+
+    // camera position, yaw and pitch at `time` seconds, clamped to the path
+    pub fn sample(&self, time: f32) -> Option<(Point3<f32>, Rad<f32>, Rad<f32>)> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        let segment = self
+            .keyframes
+            .iter()
+            .rposition(|keyframe| keyframe.time <= time)
+            .unwrap_or(0)
+            .min(last.saturating_sub(1));
+        if last == 0 {
+            let keyframe = &self.keyframes[0];
+            return Some((keyframe.position, keyframe.yaw, keyframe.pitch));
+        }
+
+        let (start, end) = (&self.keyframes[segment], &self.keyframes[segment + 1]);
+        let length = end.time - start.time;
+        let t = if length > 0.0 {
+            ((time - start.time) / length).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        // the segment end points and their neighbors, repeated at the ends of the path
+        let indices = [
+            segment.saturating_sub(1),
+            segment,
+            segment + 1,
+            (segment + 2).min(last),
+        ];
+        let [p0, p1, p2, p3] = indices.map(|i| self.keyframes[i].position);
+        let position = Point3::new(
+            catmull_rom(p0.x, p1.x, p2.x, p3.x, t),
+            catmull_rom(p0.y, p1.y, p2.y, p3.y, t),
+            catmull_rom(p0.z, p1.z, p2.z, p3.z, t),
+        );
+
+        // each yaw is moved by whole turns next to the previous one, so that the camera
+        // turns through the shortest angle
+        let mut yaws = indices.map(|i| self.keyframes[i].yaw.0);
+        for i in 1..4 {
+            yaws[i] = yaws[i - 1] + shortest_angle(yaws[i - 1], yaws[i]);
+        }
+        let yaw = catmull_rom(yaws[0], yaws[1], yaws[2], yaws[3], t);
+
+        let [q0, q1, q2, q3] = indices.map(|i| self.keyframes[i].pitch.0);
+        let pitch = catmull_rom(q0, q1, q2, q3, t).clamp(-PI / 2.0 + 0.001, PI / 2.0 - 0.001);
+
+        Some((position, Rad(yaw), Rad(pitch)))
+    }
+
+    // one keyframe per line: time x y z yaw pitch, angles in radians
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = self
+            .keyframes
+            .iter()
+            .map(|keyframe| {
+                format!(
+                    "{} {} {} {} {} {}\n",
+                    keyframe.time,
+                    keyframe.position.x,
+                    keyframe.position.y,
+                    keyframe.position.z,
+                    keyframe.yaw.0,
+                    keyframe.pitch.0
+                )
+            })
+            .collect::<String>();
+        fs::write(path, content)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid keyframe: {}", line),
+            )
+        };
+        let mut camera_path = Self::new();
+        for line in fs::read_to_string(path)?.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let values = line
+                .split_whitespace()
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid(line))?;
+            let [time, x, y, z, yaw, pitch] = values[..] else {
+                return Err(invalid(line));
+            };
+            camera_path.keyframes.push(Keyframe {
+                time,
+                position: Point3::new(x, y, z),
+                yaw: Rad(yaw),
+                pitch: Rad(pitch),
+            });
+        }
+        camera_path
+            .keyframes
+            .sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(camera_path)
+    }
+}
+
+// uniform Catmull-Rom spline between p1 and p2
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+// signed angle from `from` to `to`, in [-PI, PI)
+fn shortest_angle(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(2.0 * PI) - PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32, x: f32, yaw_degrees: f32) -> Keyframe {
+        Keyframe {
+            time,
+            position: Point3::new(x, 2.0 * x, 0.0),
+            yaw: Rad(yaw_degrees.to_radians()),
+            pitch: Rad(0.0),
+        }
+    }
+
+    fn path(keyframes: &[Keyframe]) -> CameraPath {
+        let mut path = CameraPath::new();
+        path.keyframes = keyframes.to_vec();
+        path
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn yaw_crosses_the_seam_through_the_shortest_turn() {
+        let path = path(&[keyframe(0.0, 0.0, 170.0), keyframe(2.0, 1.0, -170.0)]);
+        let (_, yaw, _) = path.sample(1.0).unwrap();
+        // halfway between 170 and 190 degrees, not back through 0
+        assert_near(shortest_angle(0.0, yaw.0).abs(), PI);
+    }
+
+    #[test]
+    fn keyframes_are_sampled_exactly_at_their_times() {
+        let keyframes = [
+            keyframe(0.0, 0.0, 0.0),
+            keyframe(2.0, 3.0, 45.0),
+            keyframe(4.0, -1.0, 90.0),
+            keyframe(6.0, 5.0, 30.0),
+        ];
+        let path = path(&keyframes);
+        for keyframe in keyframes {
+            let (position, yaw, pitch) = path.sample(keyframe.time).unwrap();
+            assert_near(position.x, keyframe.position.x);
+            assert_near(position.y, keyframe.position.y);
+            assert_near(position.z, keyframe.position.z);
+            assert_near(yaw.0, keyframe.yaw.0);
+            assert_near(pitch.0, keyframe.pitch.0);
+        }
+    }
+
+    #[test]
+    fn samples_are_clamped_to_the_path() {
+        let path = path(&[
+            keyframe(0.0, 0.0, 0.0),
+            keyframe(2.0, 3.0, 45.0),
+            keyframe(4.0, -1.0, 90.0),
+        ]);
+        let (before, _, _) = path.sample(-5.0).unwrap();
+        assert_near(before.x, 0.0);
+        let (after, yaw, _) = path.sample(10.0).unwrap();
+        assert_near(after.x, -1.0);
+        assert_near(yaw.0, 90f32.to_radians());
+        assert!(CameraPath::new().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let file = std::env::temp_dir().join(format!("camera_path_{}.txt", std::process::id()));
+        let saved = path(&[keyframe(0.0, 1.5, -30.0), keyframe(2.0, -4.0, 120.0)]);
+        saved.save(&file).unwrap();
+        let loaded = CameraPath::load(&file).unwrap();
+        assert_eq!(loaded.keyframes().len(), 2);
+        for (a, b) in saved.keyframes().iter().zip(loaded.keyframes()) {
+            assert_eq!(a.time, b.time);
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.yaw, b.yaw);
+            assert_eq!(a.pitch, b.pitch);
+        }
+
+        fs::write(&file, "0 1 2 3 0 0\n2 1 2 not_a_number 0 0\n").unwrap();
+        let error = CameraPath::load(&file).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        fs::write(&file, "0 1 2 3 0\n").unwrap();
+        assert!(CameraPath::load(&file).is_err());
+        fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const CAPTURE_DIR: &str = "captures";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    // frame_000000.png, frame_000001.png, ...
+    Png,
+    // a single uncompressed capture.y4m, 4:4:4 BT.601 limited range
+    Y4m,
+}
+
+// records every rendered frame, the simulation advancing by exactly one frame duration
+// between them whatever the time taken to render, so nothing is dropped
+pub struct FrameCapture {
+    pub frame_rate: u32,
+    pub directory: PathBuf,
+    frame_count: u32,
+    size: (u32, u32),
+    y4m_encoder: Option<y4m::Encoder<BufWriter<File>>>,
+}
+
+impl FrameCapture {
+    // frames must all be `width` x `height`
+    pub fn new(
+        directory: &Path,
+        format: CaptureFormat,
+        frame_rate: u32,
+        width: u32,
+        height: u32,
+    ) -> io::Result<Self> {
+        std::fs::create_dir_all(directory)?;
+
+        let y4m_encoder = match format {
+            CaptureFormat::Y4m => {
+                let file = File::create(directory.join("capture.y4m"))?;
+                let encoder = y4m::encode(
+                    width as usize,
+                    height as usize,
+                    y4m::Ratio::new(frame_rate as usize, 1),
+                )
+                .with_colorspace(y4m::Colorspace::C444)
+                .write_header(BufWriter::new(file))
+                .map_err(y4m_error)?;
+                Some(encoder)
+            }
+            CaptureFormat::Png => None,
+        };
+
+        Ok(Self {
+            frame_rate,
+            directory: directory.to_path_buf(),
+            frame_count: 0,
+            size: (width, height),
+            y4m_encoder,
+        })
+    }
+
+    pub fn time_step(&self) -> Duration {
+        Duration::from_secs(1) / self.frame_rate
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    pub fn write_frame(&mut self, image: &image::RgbaImage) -> io::Result<()> {
+        if image.dimensions() != self.size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "captured frame is {}x{}, expected {}x{}",
+                    image.width(),
+                    image.height(),
+                    self.size.0,
+                    self.size.1
+                ),
+            ));
+        }
+
+        match &mut self.y4m_encoder {
+            Some(encoder) => {
+                let [y, u, v] = rgb_to_ycbcr_planes(image);
+                encoder
+                    .write_frame(&y4m::Frame::new([&y, &u, &v], None))
+                    .map_err(y4m_error)?;
+            }
+            None => {
+                let path = self
+                    .directory
+                    .join(format!("frame_{:06}.png", self.frame_count));
+                image.save(&path).map_err(|e| {
+                    io::Error::other(format!("cannot save {}: {}", path.display(), e))
+                })?;
+            }
+        }
+        self.frame_count += 1;
+        Ok(())
+    }
+}
+
+fn y4m_error(error: y4m::Error) -> io::Error {
+    match error {
+        y4m::Error::IoError(e) => e,
+        e => io::Error::other(format!("{:?}", e)),
+    }
+}
+
+fn rgb_to_ycbcr_planes(image: &image::RgbaImage) -> [Vec<u8>; 3] {
+    let pixel_count = (image.width() * image.height()) as usize;
+    let mut planes = [
+        Vec::with_capacity(pixel_count),
+        Vec::with_capacity(pixel_count),
+        Vec::with_capacity(pixel_count),
+    ];
+    for pixel in image.pixels() {
+        let [r, g, b, _] = pixel.0.map(|c| c as f32);
+        let y = 16.0 + 0.257 * r + 0.504 * g + 0.098 * b;
+        let cb = 128.0 - 0.148 * r - 0.291 * g + 0.439 * b;
+        let cr = 128.0 + 0.439 * r - 0.368 * g - 0.071 * b;
+        planes[0].push(y.round() as u8);
+        planes[1].push(cb.round() as u8);
+        planes[2].push(cr.round() as u8);
+    }
+    planes
+}
@@ -0,0 +1,92 @@
+use super::{preprocessor::Preprocessor, reflection::ShaderReflection};
+
+pub struct Builder<'a> {
+    shader_filename: String,
+    entry_point: String,
+    preprocessor: Preprocessor,
+    bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    device: &'a wgpu::Device,
+}
+
+impl<'a> Builder<'a> {
+    pub fn new(device: &'a wgpu::Device) -> Self {
+        Builder {
+            shader_filename: "dummy".to_string(),
+            entry_point: "compute_main".to_string(),
+            preprocessor: Preprocessor::new(),
+            bind_group_layouts: Vec::new(),
+            device,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.bind_group_layouts.clear();
+        self.preprocessor = Preprocessor::new();
+    }
+
+    pub fn add_bind_group_layout(&mut self, layout: &'a wgpu::BindGroupLayout) {
+        self.bind_group_layouts.push(layout);
+    }
+
+    pub fn set_shader_module(&mut self, shader_filename: &str, entry_point: &str) {
+        self.shader_filename = shader_filename.to_string();
+        self.entry_point = entry_point.to_string();
+    }
+
+    #[allow(dead_code)]
+    pub fn add_define(&mut self, name: &str, value: &str) {
+        self.preprocessor.define(name, value);
+    }
+
+    pub fn build_pipeline(&mut self, label: &str) -> wgpu::ComputePipeline {
+        self.try_build_pipeline(label)
+            .unwrap_or_else(|message| panic!("{}", message))
+    }
+
+    // errors in the shader are returned instead of panicking, as for render pipelines
+    pub fn try_build_pipeline(&mut self, label: &str) -> Result<wgpu::ComputePipeline, String> {
+        let compute_pipeline = self.create_pipeline(label);
+        self.reset();
+        compute_pipeline
+    }
+
+    fn create_pipeline(&self, label: &str) -> Result<wgpu::ComputePipeline, String> {
+        let shader = self.preprocessor.try_load(&self.shader_filename)?;
+        let reflection = ShaderReflection::try_new(&shader)?;
+        if !reflection.has_entry_point(&self.entry_point, naga::ShaderStage::Compute) {
+            return Err(format!(
+                "{}: no compute entry point `{}`",
+                self.shader_filename, self.entry_point
+            ));
+        }
+        let shader_module = shader.try_create_shader_module(self.device)?;
+
+        // e.g. bind group layouts not matching the shader
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline_layout_descriptor = wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &self.bind_group_layouts,
+            push_constant_ranges: &[],
+        };
+        let pipeline_layout = self
+            .device
+            .create_pipeline_layout(&pipeline_layout_descriptor);
+
+        let compute_pipeline_descriptor = wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: &self.entry_point,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        };
+
+        let compute_pipeline = self
+            .device
+            .create_compute_pipeline(&compute_pipeline_descriptor);
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(error) => Err(format!("{}: {}", label, error)),
+            None => Ok(compute_pipeline),
+        }
+    }
+}
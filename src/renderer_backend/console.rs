@@ -0,0 +1,399 @@
+use std::collections::VecDeque;
+use std::{fs, io, path::Path};
+
+use glfw::{Action, Key, WindowEvent};
+
+use super::text::TextRenderer;
+
+const MAX_OUTPUT_LINES: usize = 256;
+// output lines shown above the prompt
+const VISIBLE_LINES: usize = 14;
+const TEXT_SCALE: f32 = 1.0;
+const MARGIN: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    Int,
+    Float,
+    // on/off, true/false or 1/0
+    Bool,
+    // hours and minutes, as 12:00, or only hours
+    Time,
+    // any word when there are no choices
+    Word(&'static [&'static str]),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f32),
+    Bool(bool),
+    Time(u32, u32),
+    Word(String),
+}
+
+// the accessors expect the kind of the parameter the value was parsed for
+impl Value {
+    pub fn int(&self) -> i64 {
+        match self {
+            Value::Int(value) => *value,
+            _ => panic!("{:?} is not an int", self),
+        }
+    }
+
+    pub fn float(&self) -> f32 {
+        match self {
+            Value::Float(value) => *value,
+            Value::Int(value) => *value as f32,
+            _ => panic!("{:?} is not a number", self),
+        }
+    }
+
+    pub fn bool(&self) -> bool {
+        match self {
+            Value::Bool(value) => *value,
+            _ => panic!("{:?} is not a bool", self),
+        }
+    }
+
+    pub fn time(&self) -> (u32, u32) {
+        match self {
+            Value::Time(hours, minutes) => (*hours, *minutes),
+            _ => panic!("{:?} is not a time", self),
+        }
+    }
+
+    pub fn word(&self) -> &str {
+        match self {
+            Value::Word(value) => value,
+            _ => panic!("{:?} is not a word", self),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+impl Param {
+    pub const fn required(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    // only after the required ones
+    pub const fn optional(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: true,
+        }
+    }
+
+    fn parse(&self, word: &str) -> Result<Value, String> {
+        let invalid =
+            |expected: &str| format!("{}: expected {}, got '{}'", self.name, expected, word);
+        match self.kind {
+            ArgKind::Int => word
+                .parse()
+                .map(Value::Int)
+                .map_err(|_| invalid("an integer")),
+            ArgKind::Float => word
+                .parse()
+                .map(Value::Float)
+                .map_err(|_| invalid("a number")),
+            ArgKind::Bool => match word {
+                "on" | "true" | "1" => Ok(Value::Bool(true)),
+                "off" | "false" | "0" => Ok(Value::Bool(false)),
+                _ => Err(invalid("on or off")),
+            },
+            ArgKind::Time => {
+                let (hours, minutes) = word.split_once(':').unwrap_or((word, "0"));
+                match (hours.parse::<u32>(), minutes.parse::<u32>()) {
+                    (Ok(hours), Ok(minutes)) if hours < 24 && minutes < 60 => {
+                        Ok(Value::Time(hours, minutes))
+                    }
+                    _ => Err(invalid("a time from 0:00 to 23:59")),
+                }
+            }
+            ArgKind::Word(choices) => {
+                if choices.is_empty() || choices.contains(&word) {
+                    Ok(Value::Word(word.to_string()))
+                } else {
+                    Err(invalid(&choices.join(" or ")))
+                }
+            }
+        }
+    }
+
+    // the words tab completion offers for this parameter
+    fn choices(&self) -> &'static [&'static str] {
+        match self.kind {
+            ArgKind::Bool => &["on", "off"],
+            ArgKind::Word(choices) => choices,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Command {
+    pub name: &'static str,
+    pub params: &'static [Param],
+    pub help: &'static str,
+}
+
+impl Command {
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.to_string();
+        for param in self.params {
+            let name = match param.kind {
+                ArgKind::Word(choices) if !choices.is_empty() => choices.join("|"),
+                _ => param.name.to_string(),
+            };
+            usage.push_str(&if param.optional {
+                format!(" [{}]", name)
+            } else {
+                format!(" <{}>", name)
+            });
+        }
+        usage
+    }
+}
+
+// drop-down command line over the top of the screen. Only reads and parses the commands,
+// which are run by the app once submitted
+pub struct Console {
+    commands: &'static [Command],
+    open: bool,
+    input: String,
+    history: Vec<String>,
+    // browsed with the arrows, history.len() being the line being typed
+    history_cursor: usize,
+    output: VecDeque<String>,
+    submitted: VecDeque<String>,
+}
+
+impl Console {
+    pub fn new(commands: &'static [Command]) -> Self {
+        Self {
+            commands,
+            open: false,
+            input: String::new(),
+            history: Vec::new(),
+            history_cursor: 0,
+            output: VecDeque::new(),
+            submitted: VecDeque::new(),
+        }
+    }
+
+    pub fn commands(&self) -> &'static [Command] {
+        self.commands
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    // to the overlay only, the oldest lines dropping off past MAX_OUTPUT_LINES
+    pub fn print(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        for line in line.lines() {
+            if self.output.len() == MAX_OUTPUT_LINES {
+                self.output.pop_front();
+            }
+            self.output.push_back(line.to_string());
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    // true when the event is used by the console. Backtick toggles it, and while open it
+    // takes every key press, releases going through so that no movement key stays held
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match *event {
+            WindowEvent::Key(Key::GraveAccent, _, Action::Press, _) => {
+                self.toggle();
+                true
+            }
+            _ if !self.open => false,
+            // typed along with the key that toggles the console
+            WindowEvent::Char('`') => true,
+            WindowEvent::Char(character) => {
+                self.input.push(character);
+                true
+            }
+            WindowEvent::Key(_, _, Action::Release, _) => false,
+            WindowEvent::Key(key, _, _, _) => {
+                match key {
+                    Key::Enter | Key::KpEnter => self.submit(),
+                    Key::Backspace => {
+                        self.input.pop();
+                    }
+                    Key::Tab => self.complete(),
+                    Key::Up => self.browse_history(-1),
+                    Key::Down => self.browse_history(1),
+                    Key::Escape => self.open = false,
+                    _ => {}
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        if line.trim().is_empty() {
+            return;
+        }
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.history_cursor = self.history.len();
+        self.submitted.push_back(line);
+    }
+
+    // the next line submitted and not yet run
+    pub fn take_submitted(&mut self) -> Option<String> {
+        self.submitted.pop_front()
+    }
+
+    fn browse_history(&mut self, offset: isize) {
+        let cursor = self.history_cursor as isize + offset;
+        if cursor < 0 || cursor > self.history.len() as isize {
+            return;
+        }
+        self.history_cursor = cursor as usize;
+        self.input = self
+            .history
+            .get(self.history_cursor)
+            .cloned()
+            .unwrap_or_default();
+    }
+
+    // completes the word being typed, a command name or a parameter choice, listing the
+    // candidates when they share nothing more
+    fn complete(&mut self) {
+        let words: Vec<String> = self.input.split_whitespace().map(str::to_string).collect();
+        let new_word = self.input.is_empty() || self.input.ends_with(' ');
+        let index = if new_word {
+            words.len()
+        } else {
+            words.len() - 1
+        };
+        let prefix = if new_word { "" } else { words[index].as_str() };
+
+        let candidates: Vec<&str> = if index == 0 {
+            self.commands.iter().map(|command| command.name).collect()
+        } else {
+            self.find(&words[0])
+                .and_then(|command| command.params.get(index - 1))
+                .map(|param| param.choices().to_vec())
+                .unwrap_or_default()
+        };
+        let candidates: Vec<&str> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .collect();
+
+        let completion = match candidates.as_slice() {
+            [] => return,
+            [candidate] => format!("{} ", candidate),
+            [first, rest @ ..] => {
+                let common = rest.iter().fold(first.len(), |common, candidate| {
+                    first
+                        .bytes()
+                        .zip(candidate.bytes())
+                        .take(common)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+                if common == prefix.len() {
+                    self.print(candidates.join("  "));
+                    return;
+                }
+                first[..common].to_string()
+            }
+        };
+        self.input.truncate(self.input.len() - prefix.len());
+        self.input.push_str(&completion);
+    }
+
+    fn find(&self, name: &str) -> Option<&'static Command> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+
+    // checks the line against the registered commands, converting each argument to the
+    // kind of its parameter
+    pub fn parse(&self, line: &str) -> Result<(&'static Command, Vec<Value>), String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let command = self
+            .find(name)
+            .ok_or_else(|| format!("unknown command '{}', try help", name))?;
+        let words: Vec<&str> = words.collect();
+        let required = command
+            .params
+            .iter()
+            .filter(|param| !param.optional)
+            .count();
+        if words.len() < required || words.len() > command.params.len() {
+            return Err(format!("usage: {}", command.usage()));
+        }
+        let values = command
+            .params
+            .iter()
+            .zip(words)
+            .map(|(param, word)| param.parse(word))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|message| format!("{}: {}", command.name, message))?;
+        Ok((command, values))
+    }
+
+    // the lines to run of a script: one command per line, '#' starting a comment
+    pub fn read_script(path: &Path) -> io::Result<Vec<String>> {
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    // queues the text of the console while open, to be drawn by the text renderer
+    pub fn draw(&self, text: &mut TextRenderer) {
+        if !self.open {
+            return;
+        }
+        let mut lines: Vec<&str> = self
+            .output
+            .iter()
+            .skip(self.output.len().saturating_sub(VISIBLE_LINES))
+            .map(String::as_str)
+            .collect();
+        let prompt = format!("> {}_", self.input);
+        lines.push(&prompt);
+        let lines = lines.join("\n");
+        // shadowed to stay readable over bright scenes
+        text.add_text(
+            &lines,
+            (MARGIN + 1.0, MARGIN + 1.0),
+            TEXT_SCALE,
+            [0.0, 0.0, 0.0, 0.8],
+        );
+        text.add_text(&lines, (MARGIN, MARGIN), TEXT_SCALE, [1.0, 1.0, 1.0, 1.0]);
+    }
+}
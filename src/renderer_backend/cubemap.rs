@@ -0,0 +1,240 @@
+use wgpu::util::DeviceExt;
+
+use super::{bind_group, bind_group_layout, hdr::HDR_FORMAT, pipeline, texture};
+
+// order of the cube layers, as expected by the GPU
+pub const FACE_NAMES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// uninitialized, wgpu fills it with zeros
+pub fn create_cubemap(
+    device: &wgpu::Device,
+    size: u32,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+    label: &str,
+) -> texture::Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    });
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some(label),
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        ..Default::default()
+    });
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some(label),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+
+    texture::Texture {
+        texture,
+        view,
+        sampler,
+        params_buffer: None,
+        bind_group: None,
+    }
+}
+
+// six square faces of the same size, in FACE_NAMES order
+pub fn from_images(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    faces: &[image::DynamicImage; 6],
+    label: &str,
+) -> texture::Texture {
+    let size = faces[0].width();
+    let cubemap = create_cubemap(
+        device,
+        size,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        label,
+    );
+
+    for (layer, face) in faces.iter().enumerate() {
+        if face.width() != size || face.height() != size {
+            panic!(
+                "{}: face {} is {}x{}, expected {}x{}",
+                label,
+                FACE_NAMES[layer],
+                face.width(),
+                face.height(),
+                size,
+                size
+            );
+        }
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &cubemap.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer as u32,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &face.to_rgba8(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(size * 4),
+                rows_per_image: Some(size),
+            },
+            wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    cubemap
+}
+
+// projects a latitude/longitude panorama, typically a .hdr file, on the six faces
+pub fn from_equirectangular(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    image: &image::DynamicImage,
+    face_size: u32,
+    label: &str,
+) -> texture::Texture {
+    let pixels = image.to_rgba32f();
+    let size = wgpu::Extent3d {
+        width: pixels.width(),
+        height: pixels.height(),
+        depth_or_array_layers: 1,
+    };
+    let equirectangular = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("Equirectangular Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        bytemuck::cast_slice(pixels.as_raw()),
+    );
+    let equirectangular_view = equirectangular.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let cubemap = create_cubemap(
+        device,
+        face_size,
+        HDR_FORMAT,
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        label,
+    );
+
+    let layout = {
+        let mut builder = bind_group_layout::Builder::new(device);
+        builder.add_texture_view(
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::TextureSampleType::Float { filterable: false },
+            wgpu::TextureViewDimension::D2,
+        );
+        builder.add_buffer(wgpu::ShaderStages::FRAGMENT);
+        builder.build("Equirectangular Bind Group Layout")
+    };
+    let projection_pipeline = {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_fullscreen_shader("shaders/equirect_to_cube.wgsl", "fragment_main");
+        builder.set_pixel_format(HDR_FORMAT);
+        builder.add_bind_group_layout(&layout);
+        builder.build_pipeline("Equirectangular Pipeline")
+    };
+
+    let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Equirectangular Encoder"),
+    });
+
+    for face in 0..6u32 {
+        let face_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cube Face Buffer"),
+            contents: bytemuck::cast_slice(&[face, 0, 0, 0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&layout);
+            builder.add_texture_view(&equirectangular_view);
+            builder.add_buffer(&face_buffer);
+            builder.build("Equirectangular Bind Group")
+        };
+        let face_view = cubemap.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Cube Face View"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: face,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Equirectangular Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &face_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&projection_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    queue.submit(std::iter::once(command_encoder.finish()));
+    cubemap
+}
+
+// `name`.hdr as a panorama, or the six faces `name`/px.png .. `name`/nz.png,
+// both under res/textures
+pub fn load(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    name: &str,
+    face_size: u32,
+) -> Option<texture::Texture> {
+    let panorama = format!("{}.hdr", name);
+    if texture::Texture::image_path(&panorama).exists() {
+        let image = texture::Texture::load_image(&panorama);
+        return Some(from_equirectangular(device, queue, &image, face_size, name));
+    }
+
+    let face_files = FACE_NAMES.map(|face| format!("{}/{}.png", name, face));
+    if face_files
+        .iter()
+        .all(|file| texture::Texture::image_path(file).exists())
+    {
+        let faces = face_files.map(|file| texture::Texture::load_image(&file));
+        return Some(from_images(device, queue, &faces, name));
+    }
+
+    None
+}
@@ -0,0 +1,248 @@
+use cgmath::{InnerSpace, Vector3};
+
+use super::{camera::Frustum, hdr::HDR_FORMAT, model::Vertex, pipeline};
+
+const SPHERE_SEGMENTS: usize = 16;
+const INITIAL_LINE_CAPACITY: usize = 4096;
+
+pub const RED: [f32; 4] = [1.0, 0.1, 0.1, 1.0];
+pub const GREEN: [f32; 4] = [0.1, 1.0, 0.1, 1.0];
+pub const BLUE: [f32; 4] = [0.1, 0.3, 1.0, 1.0];
+pub const YELLOW: [f32; 4] = [1.0, 0.9, 0.1, 1.0];
+pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl Vertex for LineVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+// what the app draws with DebugDraw, e.g. from the inspector
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebugLayers {
+    pub axes: bool,
+    pub grid: bool,
+    pub chunk_borders: bool,
+    pub bounding_spheres: bool,
+}
+
+// immediate mode lines in world space: shapes are added during the frame, uploaded before
+// the scene pass and drawn in it, depth tested against the scene. Colors are HDR
+pub struct DebugDraw {
+    pub enabled: bool,
+    vertices: Vec<LineVertex>,
+    vertex_buffer: wgpu::Buffer,
+    // vertices uploaded for the next draw
+    vertex_count: u32,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl DebugDraw {
+    // `camera_layout` is the scene camera layout, `sample_count` the one of the scene pass
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) -> Self {
+        Self {
+            enabled: false,
+            vertices: Vec::new(),
+            vertex_buffer: Self::create_vertex_buffer(device, INITIAL_LINE_CAPACITY * 2),
+            vertex_count: 0,
+            pipeline: Self::create_pipeline(device, camera_layout, sample_count),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_shader_module("shaders/debug_draw.wgsl", "vertex_main", "fragment_main");
+        builder.set_pixel_format(HDR_FORMAT);
+        builder.set_topology(wgpu::PrimitiveTopology::LineList);
+        builder.set_cull_mode(None);
+        builder.set_depth_compare(wgpu::CompareFunction::LessEqual);
+        builder.set_depth_write(false);
+        builder.set_sample_count(sample_count);
+        builder.add_vertex_buffer_layout(LineVertex::desc());
+        builder.add_bind_group_layout(camera_layout);
+        builder.build_pipeline("Debug Draw Pipeline")
+    }
+
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) {
+        self.pipeline = Self::create_pipeline(device, camera_layout, sample_count);
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, vertex_count: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Draw Vertex Buffer"),
+            size: (vertex_count * std::mem::size_of::<LineVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn line(&mut self, start: Vector3<f32>, end: Vector3<f32>, color: [f32; 4]) {
+        self.vertices
+            .extend([start, end].map(|position| LineVertex {
+                position: position.into(),
+                color,
+            }));
+    }
+
+    pub fn aabb(&mut self, min: Vector3<f32>, max: Vector3<f32>, color: [f32; 4]) {
+        let corner = |i: usize| {
+            Vector3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        // corners one bit apart share an edge
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    // three great circles, one per axis
+    pub fn sphere(&mut self, center: Vector3<f32>, radius: f32, color: [f32; 4]) {
+        let point = |axis: usize, angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            let offset = match axis {
+                0 => Vector3::new(0.0, cos, sin),
+                1 => Vector3::new(cos, 0.0, sin),
+                _ => Vector3::new(cos, sin, 0.0),
+            };
+            center + offset * radius
+        };
+        let step = std::f32::consts::TAU / SPHERE_SEGMENTS as f32;
+        for axis in 0..3 {
+            for i in 0..SPHERE_SEGMENTS {
+                let start = point(axis, i as f32 * step);
+                let end = point(axis, (i + 1) as f32 * step);
+                self.line(start, end, color);
+            }
+        }
+    }
+
+    // with a four lines head at `end`
+    pub fn arrow(&mut self, start: Vector3<f32>, end: Vector3<f32>, color: [f32; 4]) {
+        self.line(start, end, color);
+        let direction = end - start;
+        let length = direction.magnitude();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let direction = direction / length;
+        let reference = if direction.y.abs() < 0.99 {
+            Vector3::unit_y()
+        } else {
+            Vector3::unit_x()
+        };
+        let side = direction.cross(reference).normalize();
+        let up = direction.cross(side);
+        let head = length * 0.2;
+        let base = end - direction * head;
+        for offset in [side, -side, up, -up] {
+            self.line(end, base + offset * head * 0.4, color);
+        }
+    }
+
+    pub fn frustum(&mut self, frustum: &Frustum, color: [f32; 4]) {
+        let corners = frustum.corners();
+        for i in 0..4 {
+            let next = (i + 1) % 4;
+            // near face, far face and the edges between them
+            self.line(corners[i], corners[next], color);
+            self.line(corners[i + 4], corners[next + 4], color);
+            self.line(corners[i], corners[i + 4], color);
+        }
+    }
+
+    // square grid on the horizontal plane through `center`, `cells` wide on each side
+    pub fn grid(&mut self, center: Vector3<f32>, cell_size: f32, cells: u32, color: [f32; 4]) {
+        let extent = cell_size * cells as f32;
+        for i in 0..=cells * 2 {
+            let offset = i as f32 * cell_size - extent;
+            self.line(
+                center + Vector3::new(offset, 0.0, -extent),
+                center + Vector3::new(offset, 0.0, extent),
+                color,
+            );
+            self.line(
+                center + Vector3::new(-extent, 0.0, offset),
+                center + Vector3::new(extent, 0.0, offset),
+                color,
+            );
+        }
+    }
+
+    // x in red, y in green and z in blue
+    pub fn axes(&mut self, origin: Vector3<f32>, length: f32) {
+        self.arrow(origin, origin + Vector3::unit_x() * length, RED);
+        self.arrow(origin, origin + Vector3::unit_y() * length, GREEN);
+        self.arrow(origin, origin + Vector3::unit_z() * length, BLUE);
+    }
+
+    // uploads what was added since the last call, to be drawn by the next draw
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let vertex_size = std::mem::size_of::<LineVertex>() as wgpu::BufferAddress;
+        if self.vertices.len() as wgpu::BufferAddress * vertex_size > self.vertex_buffer.size() {
+            self.vertex_buffer =
+                Self::create_vertex_buffer(device, self.vertices.len().next_power_of_two());
+        }
+        if !self.vertices.is_empty() {
+            queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        }
+        self.vertex_count = self.vertices.len() as u32;
+        self.vertices.clear();
+    }
+
+    pub fn has_lines(&self) -> bool {
+        self.vertex_count > 0
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,264 @@
+use std::time::Instant;
+
+use glfw::{Action, Window, WindowEvent};
+
+// egui panels drawn over the final image, fed with the glfw events of the window.
+// While open the cursor is released, and the events used by the UI are not meant to reach
+// the camera
+pub struct DebugUi {
+    pub context: egui::Context,
+    renderer: egui_wgpu::Renderer,
+    open: bool,
+    // gathered between frames
+    input: egui::RawInput,
+    modifiers: egui::Modifiers,
+    pointer_position: egui::Pos2,
+    start: Instant,
+    output: Option<egui::FullOutput>,
+}
+
+impl DebugUi {
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat) -> Self {
+        Self {
+            context: egui::Context::default(),
+            renderer: egui_wgpu::Renderer::new(device, output_format, None, 1, false),
+            open: false,
+            input: egui::RawInput::default(),
+            modifiers: egui::Modifiers::default(),
+            pointer_position: egui::Pos2::ZERO,
+            start: Instant::now(),
+            output: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // shows the cursor while open, hides and captures it again for the camera otherwise
+    pub fn toggle(&mut self, window: &mut Window) {
+        self.open = !self.open;
+        window.set_cursor_mode(if self.open {
+            glfw::CursorMode::Normal
+        } else {
+            glfw::CursorMode::Disabled
+        });
+        self.input.events.clear();
+    }
+
+    // physical pixels per egui point
+    fn pixels_per_point(window: &Window) -> f32 {
+        window.get_content_scale().0.max(0.5)
+    }
+
+    // true when the event is used by the UI. Cursor moves are always used while open since
+    // the camera should not turn with a visible cursor
+    pub fn handle_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        if !self.open {
+            return false;
+        }
+        match *event {
+            WindowEvent::CursorPos(x, y) => {
+                // cursor positions are in screen coordinates, which are not always pixels
+                let (width, _) = window.get_size();
+                let (framebuffer_width, _) = window.get_framebuffer_size();
+                let scale =
+                    framebuffer_width as f32 / width.max(1) as f32 / Self::pixels_per_point(window);
+                self.pointer_position = egui::pos2(x as f32 * scale, y as f32 * scale);
+                self.input
+                    .events
+                    .push(egui::Event::PointerMoved(self.pointer_position));
+                true
+            }
+            WindowEvent::MouseButton(button, action, _) => {
+                let button = match button {
+                    glfw::MouseButtonLeft => egui::PointerButton::Primary,
+                    glfw::MouseButtonRight => egui::PointerButton::Secondary,
+                    glfw::MouseButtonMiddle => egui::PointerButton::Middle,
+                    _ => return false,
+                };
+                self.input.events.push(egui::Event::PointerButton {
+                    pos: self.pointer_position,
+                    button,
+                    pressed: action != Action::Release,
+                    modifiers: self.modifiers,
+                });
+                self.context.is_pointer_over_area() || self.context.wants_pointer_input()
+            }
+            WindowEvent::Scroll(x, y) => {
+                self.input.events.push(egui::Event::MouseWheel {
+                    unit: egui::MouseWheelUnit::Line,
+                    delta: egui::vec2(x as f32, y as f32),
+                    modifiers: self.modifiers,
+                });
+                self.context.is_pointer_over_area()
+            }
+            WindowEvent::Char(character) => {
+                self.input
+                    .events
+                    .push(egui::Event::Text(character.to_string()));
+                self.context.wants_keyboard_input()
+            }
+            WindowEvent::Key(key, _, action, modifiers) => {
+                self.modifiers = egui::Modifiers {
+                    alt: modifiers.contains(glfw::Modifiers::Alt),
+                    ctrl: modifiers.contains(glfw::Modifiers::Control),
+                    shift: modifiers.contains(glfw::Modifiers::Shift),
+                    mac_cmd: false,
+                    command: modifiers.contains(glfw::Modifiers::Control),
+                };
+                if let Some(key) = Self::egui_key(key) {
+                    self.input.events.push(egui::Event::Key {
+                        key,
+                        physical_key: None,
+                        pressed: action != Action::Release,
+                        repeat: action == Action::Repeat,
+                        modifiers: self.modifiers,
+                    });
+                }
+                self.context.wants_keyboard_input()
+            }
+            _ => false,
+        }
+    }
+
+    // the keys used to edit text and move between widgets
+    fn egui_key(key: glfw::Key) -> Option<egui::Key> {
+        use glfw::Key;
+        Some(match key {
+            Key::Left => egui::Key::ArrowLeft,
+            Key::Right => egui::Key::ArrowRight,
+            Key::Up => egui::Key::ArrowUp,
+            Key::Down => egui::Key::ArrowDown,
+            Key::Backspace => egui::Key::Backspace,
+            Key::Delete => egui::Key::Delete,
+            Key::Enter | Key::KpEnter => egui::Key::Enter,
+            Key::Tab => egui::Key::Tab,
+            Key::Escape => egui::Key::Escape,
+            Key::Home => egui::Key::Home,
+            Key::End => egui::Key::End,
+            Key::PageUp => egui::Key::PageUp,
+            Key::PageDown => egui::Key::PageDown,
+            Key::Space => egui::Key::Space,
+            // letters for the copy, paste and select all shortcuts
+            _ => return egui::Key::from_name(&format!("{:?}", key)),
+        })
+    }
+
+    // combo box over the values reached by `next` from `value`
+    pub fn enum_combo<T: Copy + PartialEq + std::fmt::Debug>(
+        ui: &mut egui::Ui,
+        label: &str,
+        value: &mut T,
+        next: impl Fn(T) -> T,
+    ) -> bool {
+        let mut changed = false;
+        egui::ComboBox::from_label(label)
+            .selected_text(format!("{:?}", value))
+            .show_ui(ui, |ui| {
+                let first = *value;
+                let mut option = first;
+                loop {
+                    changed |= ui
+                        .selectable_value(value, option, format!("{:?}", option))
+                        .changed();
+                    option = next(option);
+                    if option == first {
+                        break;
+                    }
+                }
+            });
+        changed
+    }
+
+    // starts a frame of the UI, to be filled through the returned context before end_frame
+    pub fn begin_frame(&mut self, window: &Window) -> egui::Context {
+        let (width, height) = window.get_framebuffer_size();
+        let pixels_per_point = Self::pixels_per_point(window);
+        let mut input = std::mem::take(&mut self.input);
+        input.screen_rect = Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(width as f32, height as f32) / pixels_per_point,
+        ));
+        input.time = Some(self.start.elapsed().as_secs_f64());
+        input.modifiers = self.modifiers;
+        input
+            .viewports
+            .entry(egui::ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(pixels_per_point);
+        self.context.begin_pass(input);
+        self.context.clone()
+    }
+
+    pub fn end_frame(&mut self) {
+        let mut output = self.context.end_pass();
+        // the textures of a frame that was not drawn are still to be uploaded
+        if let Some(previous) = self.output.take() {
+            let mut textures_delta = previous.textures_delta;
+            textures_delta.append(output.textures_delta);
+            output.textures_delta = textures_delta;
+        }
+        self.output = Some(output);
+    }
+
+    // draws the last finished frame over `output`, of `size` pixels
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        command_encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        size: (u32, u32),
+    ) {
+        let Some(full_output) = self.output.take() else {
+            return;
+        };
+        let pixels_per_point = full_output.pixels_per_point;
+        let screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [size.0, size.1],
+            pixels_per_point,
+        };
+        for (id, image_delta) in &full_output.textures_delta.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+        }
+        let paint_jobs = self
+            .context
+            .tessellate(full_output.shapes, pixels_per_point);
+        // only paint callbacks record command buffers of their own, and none are used
+        self.renderer.update_buffers(
+            device,
+            queue,
+            command_encoder,
+            &paint_jobs,
+            &screen_descriptor,
+        );
+
+        {
+            let render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug UI Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.renderer.render(
+                &mut render_pass.forget_lifetime(),
+                &paint_jobs,
+                &screen_descriptor,
+            );
+        }
+
+        for id in &full_output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use super::{bind_group, bind_group_layout, pipeline};
+
+// what the scene pass shows, the discriminants are the DEBUG_VIEW values of shader.wgsl
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    Lit = 0,
+    // lit, with the triangle edges over it
+    Wireframe = 1,
+    // zero length normals, e.g. from an OBJ without any, are magenta
+    Normals = 2,
+    // depth buffer value brought back to a distance, black at the camera and white at the
+    // far plane
+    Depth = 3,
+    UvChecker = 4,
+    // a color per instance, and per chunk for the voxel meshes
+    InstanceColor = 5,
+    // fragments drawn per pixel, hidden ones included, from blue to red
+    Overdraw = 6,
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::Lit => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::Normals,
+            DebugView::Normals => DebugView::Depth,
+            DebugView::Depth => DebugView::UvChecker,
+            DebugView::UvChecker => DebugView::InstanceColor,
+            DebugView::InstanceColor => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::Lit,
+        }
+    }
+
+    // every view but these draws the scene with its own pipelines only, shown as is
+    // instead of being lit, post processed and tone mapped
+    pub fn replaces_shading(self) -> bool {
+        !matches!(self, DebugView::Lit | DebugView::Wireframe)
+    }
+
+    // every view but Lit
+    pub fn all() -> impl Iterator<Item = DebugView> {
+        std::iter::successors(Some(DebugView::Lit.next()), |view| {
+            Some(view.next()).filter(|view| *view != DebugView::Lit)
+        })
+    }
+}
+
+// the selected view and everything drawing it, the scene pipelines of a view being built
+// the first time it is selected
+pub struct DebugState {
+    pub view: DebugView,
+    pass: DebugViewPass,
+    // instanced and voxel pipelines of the views selected since the last clear_pipelines
+    pipelines: HashMap<DebugView, (wgpu::RenderPipeline, wgpu::RenderPipeline)>,
+}
+
+impl DebugState {
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat) -> Self {
+        Self {
+            view: DebugView::Lit,
+            pass: DebugViewPass::new(device, output_format),
+            pipelines: HashMap::new(),
+        }
+    }
+
+    // `builders` returns the lit instanced and voxel scene pipeline builders, only called
+    // when the selected view has no pipelines yet
+    pub fn build_pipelines<'a>(
+        &mut self,
+        builders: impl FnOnce() -> [pipeline::Builder<'a>; 2],
+    ) -> Result<(), String> {
+        if self.view == DebugView::Lit || self.pipelines.contains_key(&self.view) {
+            return Ok(());
+        }
+        let [mut instanced, mut voxel] = builders();
+        configure_pipeline(&mut instanced, self.view);
+        configure_pipeline(&mut voxel, self.view);
+        let instanced =
+            instanced.try_build_pipeline(&format!("{:?} Debug View Pipeline", self.view))?;
+        let voxel =
+            voxel.try_build_pipeline(&format!("{:?} Debug View Voxel Pipeline", self.view))?;
+        self.pipelines.insert(self.view, (instanced, voxel));
+        Ok(())
+    }
+
+    // None for Lit and for a view whose pipelines are not built yet
+    pub fn pipelines(&self) -> Option<&(wgpu::RenderPipeline, wgpu::RenderPipeline)> {
+        self.pipelines.get(&self.view)
+    }
+
+    // when the scene shader, its layouts or the sample count change
+    pub fn clear_pipelines(&mut self) {
+        self.pipelines.clear();
+    }
+
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        self.pass
+            .draw(device, command_encoder, source, output, self.view);
+    }
+}
+
+// turns a scene pipeline, instanced or voxel, into the one drawing `view`
+pub fn configure_pipeline(builder: &mut pipeline::Builder, view: DebugView) {
+    if view == DebugView::Lit {
+        return;
+    }
+    builder.add_define("DEBUG_VIEW", &format!("{}u", view as u32));
+    match view {
+        // drawn over the lit scene, a bit in front of the faces it outlines
+        DebugView::Wireframe => {
+            builder.set_polygon_mode(wgpu::PolygonMode::Line);
+            builder.set_depth_compare(wgpu::CompareFunction::LessEqual);
+            builder.set_depth_write(false);
+            builder.set_depth_bias(-2, -1.0, 0.0);
+        }
+        // every fragment adds one to the target
+        DebugView::Overdraw => {
+            builder.set_blend_preset(pipeline::BlendPreset::Additive);
+            builder.set_depth_compare(wgpu::CompareFunction::Always);
+            builder.set_depth_write(false);
+        }
+        _ => {}
+    }
+}
+
+// copies the HDR target of the views replacing the shading into the surface, mapping the
+// overdraw counts to colors
+struct DebugViewPass {
+    layout: wgpu::BindGroupLayout,
+    copy_pipeline: wgpu::RenderPipeline,
+    heatmap_pipeline: wgpu::RenderPipeline,
+}
+
+impl DebugViewPass {
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat) -> Self {
+        let layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_texture_view(
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+            );
+            builder.build("Debug View Bind Group Layout")
+        };
+        let build = |heatmap: bool, label: &str| {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_fullscreen_shader("shaders/debug_view.wgsl", "fragment_main");
+            if heatmap {
+                builder.add_define("HEATMAP", "");
+            }
+            builder.set_pixel_format(output_format);
+            builder.add_bind_group_layout(&layout);
+            builder.build_pipeline(label)
+        };
+        let copy_pipeline = build(false, "Debug View Pipeline");
+        let heatmap_pipeline = build(true, "Debug View Heatmap Pipeline");
+
+        Self {
+            layout,
+            copy_pipeline,
+            heatmap_pipeline,
+        }
+    }
+
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+        view: DebugView,
+    ) {
+        // the HDR target is recreated on resize
+        let bind_group = {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&self.layout);
+            builder.add_texture_view(source);
+            builder.build("Debug View Bind Group")
+        };
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug View Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(if view == DebugView::Overdraw {
+            &self.heatmap_pipeline
+        } else {
+            &self.copy_pipeline
+        });
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,231 @@
+use cgmath::Vector3;
+
+use super::{bind_group, reflection::ShaderReflection};
+
+// lighting of the scene, see time_of_day for how it changes over a day
+#[derive(Debug, Clone, Copy)]
+pub struct Lighting {
+    // towards the sun
+    pub sun_direction: Vector3<f32>,
+    // radiance of the sun, zero once it has set
+    pub sun_color: Vector3<f32>,
+    pub ambient_color: Vector3<f32>,
+    pub horizon_color: Vector3<f32>,
+    pub zenith_color: Vector3<f32>,
+    pub fog_color: Vector3<f32>,
+}
+
+impl Lighting {
+    pub fn clear_color(&self) -> wgpu::Color {
+        wgpu::Color {
+            r: self.horizon_color.x as f64,
+            g: self.horizon_color.y as f64,
+            b: self.horizon_color.z as f64,
+            a: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FogMode {
+    Disabled = 0,
+    Linear = 1,
+    Exponential = 2,
+    ExponentialSquared = 3,
+}
+
+impl FogMode {
+    pub fn next(self) -> Self {
+        match self {
+            FogMode::Disabled => FogMode::Linear,
+            FogMode::Linear => FogMode::Exponential,
+            FogMode::Exponential => FogMode::ExponentialSquared,
+            FogMode::ExponentialSquared => FogMode::Disabled,
+        }
+    }
+}
+
+// distance fog plus a layer of height fog thinning out above `base_height`,
+// tinted with Lighting::fog_color
+#[derive(Debug, Clone, Copy)]
+pub struct Fog {
+    pub mode: FogMode,
+    // used by the linear mode
+    pub start: f32,
+    pub end: f32,
+    // used by the exponential modes
+    pub density: f32,
+    // zero disables the height fog
+    pub height_density: f32,
+    pub height_falloff: f32,
+    pub base_height: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            mode: FogMode::ExponentialSquared,
+            start: 50.0,
+            end: 250.0,
+            density: 0.008,
+            height_density: 0.02,
+            height_falloff: 0.1,
+            base_height: 0.0,
+        }
+    }
+}
+
+impl Fog {
+    // distance at which the distance fog hides everything, e.g. to use as far plane
+    pub fn visibility_distance(&self) -> Option<f32> {
+        // exponential fog never reaches 1, stop at 99%
+        let opaque = 100f32.ln();
+        match self.mode {
+            FogMode::Disabled => None,
+            FogMode::Linear => Some(self.end),
+            FogMode::Exponential => Some(opaque / self.density),
+            FogMode::ExponentialSquared => Some(opaque.sqrt() / self.density),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FogUniform {
+    mode: u32,
+    start: f32,
+    end: f32,
+    density: f32,
+    height_density: f32,
+    height_falloff: f32,
+    base_height: f32,
+    _padding: f32,
+}
+
+impl FogUniform {
+    fn new(fog: &Fog) -> Self {
+        Self {
+            mode: fog.mode as u32,
+            start: fog.start,
+            end: fog.end,
+            density: fog.density,
+            height_density: fog.height_density,
+            height_falloff: fog.height_falloff,
+            base_height: fog.base_height,
+            _padding: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightingUniform {
+    sun_direction: [f32; 4],
+    sun_color: [f32; 4],
+    ambient_color: [f32; 4],
+    fog_color: [f32; 4],
+}
+
+impl LightingUniform {
+    fn new(lighting: &Lighting) -> Self {
+        Self {
+            sun_direction: lighting.sun_direction.extend(0.0).into(),
+            sun_color: lighting.sun_color.extend(1.0).into(),
+            ambient_color: lighting.ambient_color.extend(1.0).into(),
+            fog_color: lighting.fog_color.extend(1.0).into(),
+        }
+    }
+}
+
+// uniforms describing the world around the geometry, matching `Lighting` and `Fog`
+// in shaders/common/lighting.wgsl and shaders/common/fog.wgsl
+pub struct Environment {
+    lighting_buffer: wgpu::Buffer,
+    fog_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Environment {
+    // `layout` being reflected from `reflections`, see bind_group::Builder::set_reflected_layout
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        reflections: &[ShaderReflection],
+        group: u32,
+    ) -> Self {
+        let lighting_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lighting Buffer"),
+            size: std::mem::size_of::<LightingUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let fog_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Fog Buffer"),
+            size: std::mem::size_of::<FogUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = Self::create_bind_group(
+            device,
+            layout,
+            reflections,
+            group,
+            &lighting_buffer,
+            &fog_buffer,
+        );
+
+        Self {
+            lighting_buffer,
+            fog_buffer,
+            bind_group,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        reflections: &[ShaderReflection],
+        group: u32,
+        lighting_buffer: &wgpu::Buffer,
+        fog_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_reflected_layout(layout, reflections, group);
+        builder.bind("lighting", lighting_buffer.as_entire_binding());
+        builder.bind("fog", fog_buffer.as_entire_binding());
+        builder.build("Environment Bind Group")
+    }
+
+    // once the layout is created again, e.g. from a reloaded shader
+    pub fn set_layout(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        reflections: &[ShaderReflection],
+        group: u32,
+    ) {
+        self.bind_group = Self::create_bind_group(
+            device,
+            layout,
+            reflections,
+            group,
+            &self.lighting_buffer,
+            &self.fog_buffer,
+        );
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, lighting: &Lighting, fog: &Fog) {
+        queue.write_buffer(
+            &self.lighting_buffer,
+            0,
+            bytemuck::cast_slice(&[LightingUniform::new(lighting)]),
+        );
+        queue.write_buffer(
+            &self.fog_buffer,
+            0,
+            bytemuck::cast_slice(&[FogUniform::new(fog)]),
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use super::{bind_group, bind_group_layout, metrics, pipeline};
+
+const SAMPLE_COUNT: usize = 128;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FrameGraphUniform {
+    rect: [f32; 4],
+    max_ms: f32,
+    budget_ms: f32,
+    sample_count: u32,
+    _padding: f32,
+    cpu_ms: [f32; SAMPLE_COUNT],
+    gpu_ms: [f32; SAMPLE_COUNT],
+}
+
+// bar graph of the last frame times, drawn over the final image
+pub struct FrameGraph {
+    pub enabled: bool,
+    // milliseconds of a frame at the target frame rate
+    pub budget_ms: f32,
+    // left, bottom, width and height in clip space
+    pub rect: [f32; 4],
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl FrameGraph {
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Graph Buffer"),
+            size: std::mem::size_of::<FrameGraphUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_buffer(wgpu::ShaderStages::VERTEX_FRAGMENT);
+            builder.build("Frame Graph Bind Group Layout")
+        };
+        let bind_group = {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&layout);
+            builder.add_buffer(&uniform_buffer);
+            builder.build("Frame Graph Bind Group")
+        };
+        let pipeline = {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_shader_module("shaders/frame_graph.wgsl", "vertex_main", "fragment_main");
+            builder.set_pixel_format(output_format);
+            builder.set_blend_preset(pipeline::BlendPreset::Alpha);
+            builder.set_cull_mode(None);
+            builder.disable_depth();
+            builder.add_bind_group_layout(&layout);
+            builder.build_pipeline("Frame Graph Pipeline")
+        };
+
+        Self {
+            enabled: false,
+            budget_ms: 1000.0 / 60.0,
+            rect: [-0.98, -0.98, 0.6, 0.25],
+            uniform_buffer,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, metrics: &metrics::Metrics) {
+        if !self.enabled {
+            return;
+        }
+        let history = metrics.history();
+        let mut uniform = FrameGraphUniform {
+            rect: self.rect,
+            max_ms: self.budget_ms * 3.0,
+            budget_ms: self.budget_ms,
+            sample_count: history.len().min(SAMPLE_COUNT) as u32,
+            _padding: 0.0,
+            cpu_ms: [0.0; SAMPLE_COUNT],
+            gpu_ms: [0.0; SAMPLE_COUNT],
+        };
+        let recent = history
+            .iter()
+            .skip(history.len().saturating_sub(SAMPLE_COUNT));
+        // held over the frames without a GPU readback
+        let mut gpu_ms = 0.0;
+        for (i, stats) in recent.enumerate() {
+            uniform.cpu_ms[i] = stats.frame_time.as_secs_f32() * 1000.0;
+            if !stats.gpu_passes.is_empty() {
+                gpu_ms = stats.gpu_time();
+            }
+            uniform.gpu_ms[i] = gpu_ms;
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn draw(&self, command_encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        if !self.enabled {
+            return;
+        }
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Frame Graph Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
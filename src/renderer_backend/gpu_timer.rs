@@ -0,0 +1,177 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+// measures the duration of render passes with timestamp queries, the results of a frame
+// being read back a few frames later without stalling.
+// Does nothing when the device lacks TIMESTAMP_QUERY
+pub struct GpuTimer {
+    queries: Option<Queries>,
+    // passes timed in the current frame
+    labels: Vec<&'static str>,
+    // labels and milliseconds of the last read back frame, until taken
+    timings: Vec<(&'static str, f32)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Readback {
+    // the next frame can be timed
+    Idle,
+    // copied into the readback buffer, to be mapped once submitted
+    Resolved,
+    // waiting for the map callback
+    Mapping,
+}
+
+struct Queries {
+    query_set: wgpu::QuerySet,
+    count: u32,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    readback: Readback,
+    // set by the map callback
+    mapped: Arc<AtomicBool>,
+    // nanoseconds per tick
+    period: f32,
+}
+
+impl GpuTimer {
+    // one begin and one end timestamp per pass
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, max_passes: u32) -> Self {
+        let queries = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let count = max_passes * 2;
+                let size = (count as usize * std::mem::size_of::<u64>()) as wgpu::BufferAddress;
+                Queries {
+                    query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("GPU Timer Query Set"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count,
+                    }),
+                    count,
+                    resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("GPU Timer Resolve Buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("GPU Timer Readback Buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    }),
+                    readback: Readback::Idle,
+                    mapped: Arc::new(AtomicBool::new(false)),
+                    period: queue.get_timestamp_period(),
+                }
+            });
+        Self {
+            queries,
+            labels: Vec::with_capacity(max_passes as usize),
+            timings: Vec::new(),
+        }
+    }
+
+    // false without TIMESTAMP_QUERY, every pass then going untimed
+    pub fn is_enabled(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    // pass labels and their milliseconds, a few frames late. Only once per readback, empty
+    // for the frames in between
+    pub fn take_timings(&mut self) -> Vec<(&'static str, f32)> {
+        std::mem::take(&mut self.timings)
+    }
+
+    // to be called before the passes of a frame are recorded
+    pub fn begin_frame(&mut self, device: &wgpu::Device) {
+        let Some(queries) = &mut self.queries else {
+            return;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        if queries.readback == Readback::Mapping && queries.mapped.load(Ordering::Acquire) {
+            {
+                let data = queries.readback_buffer.slice(..).get_mapped_range();
+                let ticks: &[u64] = bytemuck::cast_slice(&data);
+                self.timings = self
+                    .labels
+                    .iter()
+                    .zip(ticks.chunks_exact(2))
+                    .map(|(label, pair)| {
+                        let ticks = pair[1].wrapping_sub(pair[0]);
+                        (*label, ticks as f32 * queries.period / 1e6)
+                    })
+                    .collect();
+            }
+            queries.readback_buffer.unmap();
+            queries.mapped.store(false, Ordering::Release);
+            queries.readback = Readback::Idle;
+        }
+        if queries.readback == Readback::Idle {
+            self.labels.clear();
+        }
+    }
+
+    // the timestamp writes of a render pass, None when disabled, when every query is used
+    // or while the previous results are still being read back
+    pub fn pass_timestamps(
+        &mut self,
+        label: &'static str,
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let queries = self.queries.as_ref()?;
+        let index = self.labels.len() as u32;
+        if queries.readback != Readback::Idle || index * 2 >= queries.count {
+            return None;
+        }
+        self.labels.push(label);
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &queries.query_set,
+            beginning_of_pass_write_index: Some(index * 2),
+            end_of_pass_write_index: Some(index * 2 + 1),
+        })
+    }
+
+    // resolves the queries of the frame, after its last timed pass
+    pub fn resolve(&mut self, command_encoder: &mut wgpu::CommandEncoder) {
+        let Some(queries) = &mut self.queries else {
+            return;
+        };
+        if queries.readback != Readback::Idle || self.labels.is_empty() {
+            return;
+        }
+        let count = self.labels.len() as u32 * 2;
+        command_encoder.resolve_query_set(&queries.query_set, 0..count, &queries.resolve_buffer, 0);
+        command_encoder.copy_buffer_to_buffer(
+            &queries.resolve_buffer,
+            0,
+            &queries.readback_buffer,
+            0,
+            queries.resolve_buffer.size(),
+        );
+        queries.readback = Readback::Resolved;
+    }
+
+    // to be called once the frame is submitted
+    pub fn end_frame(&mut self) {
+        let Some(queries) = &mut self.queries else {
+            return;
+        };
+        if queries.readback != Readback::Resolved {
+            return;
+        }
+        queries.readback = Readback::Mapping;
+        let mapped = queries.mapped.clone();
+        queries
+            .readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
+            });
+    }
+}
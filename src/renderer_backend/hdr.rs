@@ -0,0 +1,306 @@
+use std::time::Duration;
+
+use wgpu::util::DeviceExt;
+
+use super::{
+    bind_group, bind_group_layout, compute_pipeline, pipeline, preprocessor::Preprocessor,
+    reflection::ShaderReflection, texture,
+};
+
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+const MIN_LOG_LUMINANCE: f32 = -8.0;
+const LOG_LUMINANCE_RANGE: f32 = 12.0;
+// higher adapts faster, in 1/seconds
+const ADAPTATION_SPEED: f32 = 1.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapping {
+    Reinhard = 0,
+    Aces = 1,
+    AgX = 2,
+}
+
+impl ToneMapping {
+    pub fn next(self) -> Self {
+        match self {
+            ToneMapping::Reinhard => ToneMapping::Aces,
+            ToneMapping::Aces => ToneMapping::AgX,
+            ToneMapping::AgX => ToneMapping::Reinhard,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ToneMapUniform {
+    exposure: f32,
+    tone_mapping: u32,
+    auto_exposure: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct HistogramUniform {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    adaptation: f32,
+    _padding: f32,
+}
+
+// the scene renders into `target`, which is then tone mapped into the surface
+pub struct HdrPipeline {
+    pub target: texture::Texture,
+    pub tone_mapping: ToneMapping,
+    pub exposure: f32,
+    pub auto_exposure: bool,
+    width: u32,
+    height: u32,
+    tone_map_buffer: wgpu::Buffer,
+    tone_map_layout: wgpu::BindGroupLayout,
+    tone_map_reflection: ShaderReflection,
+    tone_map_bind_group: wgpu::BindGroup,
+    tone_map_pipeline: wgpu::RenderPipeline,
+    histogram_buffer: wgpu::Buffer,
+    histogram_params_buffer: wgpu::Buffer,
+    luminance_buffer: wgpu::Buffer,
+    histogram_layout: wgpu::BindGroupLayout,
+    histogram_reflection: ShaderReflection,
+    histogram_bind_group: wgpu::BindGroup,
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+}
+
+impl HdrPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        output_format: wgpu::TextureFormat,
+    ) -> Self {
+        let target =
+            texture::Texture::create_render_target(device, width, height, HDR_FORMAT, "HDR Target");
+
+        let tone_map_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tone Map Buffer"),
+            size: std::mem::size_of::<ToneMapUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let histogram_params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Histogram Params Buffer"),
+            size: std::mem::size_of::<HistogramUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Histogram Buffer"),
+            size: 256 * std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let luminance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Average Luminance Buffer"),
+            contents: bytemuck::cast_slice(&[0.18f32]),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let tone_map_reflection =
+            ShaderReflection::load("shaders/tonemap.wgsl", &Preprocessor::new());
+        let tone_map_layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_reflected_entries(std::slice::from_ref(&tone_map_reflection), 0);
+            builder.build("Tone Map Bind Group Layout")
+        };
+        let tone_map_pipeline = {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_fullscreen_shader("shaders/tonemap.wgsl", "fragment_main");
+            builder.set_pixel_format(output_format);
+            builder.add_bind_group_layout(&tone_map_layout);
+            builder.build_pipeline("Tone Map Pipeline")
+        };
+
+        let histogram_reflection =
+            ShaderReflection::load("shaders/luminance_histogram.wgsl", &Preprocessor::new());
+        let histogram_layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_reflected_entries(std::slice::from_ref(&histogram_reflection), 0);
+            builder.build("Histogram Bind Group Layout")
+        };
+        let mut builder = compute_pipeline::Builder::new(device);
+        builder.set_shader_module("shaders/luminance_histogram.wgsl", "histogram_main");
+        builder.add_bind_group_layout(&histogram_layout);
+        let histogram_pipeline = builder.build_pipeline("Histogram Pipeline");
+        builder.set_shader_module("shaders/luminance_histogram.wgsl", "average_main");
+        builder.add_bind_group_layout(&histogram_layout);
+        let average_pipeline = builder.build_pipeline("Average Luminance Pipeline");
+
+        let tone_map_bind_group = Self::create_tone_map_bind_group(
+            device,
+            &tone_map_layout,
+            &tone_map_reflection,
+            &target,
+            &tone_map_buffer,
+            &luminance_buffer,
+        );
+        let histogram_bind_group = Self::create_histogram_bind_group(
+            device,
+            &histogram_layout,
+            &histogram_reflection,
+            &target,
+            &histogram_params_buffer,
+            &histogram_buffer,
+            &luminance_buffer,
+        );
+
+        Self {
+            target,
+            tone_mapping: ToneMapping::Aces,
+            exposure: 1.0,
+            auto_exposure: false,
+            width,
+            height,
+            tone_map_buffer,
+            tone_map_layout,
+            tone_map_reflection,
+            tone_map_bind_group,
+            tone_map_pipeline,
+            histogram_buffer,
+            histogram_params_buffer,
+            luminance_buffer,
+            histogram_layout,
+            histogram_reflection,
+            histogram_bind_group,
+            histogram_pipeline,
+            average_pipeline,
+        }
+    }
+
+    fn create_tone_map_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        reflection: &ShaderReflection,
+        target: &texture::Texture,
+        tone_map_buffer: &wgpu::Buffer,
+        luminance_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_reflected_layout(layout, std::slice::from_ref(reflection), 0);
+        builder.bind(
+            "hdr_texture",
+            wgpu::BindingResource::TextureView(&target.view),
+        );
+        builder.bind("params", tone_map_buffer.as_entire_binding());
+        builder.bind("average_luminance", luminance_buffer.as_entire_binding());
+        builder.build("Tone Map Bind Group")
+    }
+
+    fn create_histogram_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        reflection: &ShaderReflection,
+        target: &texture::Texture,
+        histogram_params_buffer: &wgpu::Buffer,
+        histogram_buffer: &wgpu::Buffer,
+        luminance_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_reflected_layout(layout, std::slice::from_ref(reflection), 0);
+        builder.bind(
+            "hdr_texture",
+            wgpu::BindingResource::TextureView(&target.view),
+        );
+        builder.bind("params", histogram_params_buffer.as_entire_binding());
+        builder.bind("histogram", histogram_buffer.as_entire_binding());
+        builder.bind("average_luminance", luminance_buffer.as_entire_binding());
+        builder.build("Histogram Bind Group")
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.target =
+            texture::Texture::create_render_target(device, width, height, HDR_FORMAT, "HDR Target");
+        self.tone_map_bind_group = Self::create_tone_map_bind_group(
+            device,
+            &self.tone_map_layout,
+            &self.tone_map_reflection,
+            &self.target,
+            &self.tone_map_buffer,
+            &self.luminance_buffer,
+        );
+        self.histogram_bind_group = Self::create_histogram_bind_group(
+            device,
+            &self.histogram_layout,
+            &self.histogram_reflection,
+            &self.target,
+            &self.histogram_params_buffer,
+            &self.histogram_buffer,
+            &self.luminance_buffer,
+        );
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, delta_time: Duration) {
+        let tone_map_uniform = ToneMapUniform {
+            exposure: self.exposure,
+            tone_mapping: self.tone_mapping as u32,
+            auto_exposure: self.auto_exposure as u32,
+            _padding: 0,
+        };
+        queue.write_buffer(
+            &self.tone_map_buffer,
+            0,
+            bytemuck::cast_slice(&[tone_map_uniform]),
+        );
+
+        let adaptation = 1.0 - (-delta_time.as_secs_f32() * ADAPTATION_SPEED).exp();
+        let histogram_uniform = HistogramUniform {
+            min_log_luminance: MIN_LOG_LUMINANCE,
+            log_luminance_range: LOG_LUMINANCE_RANGE,
+            adaptation: adaptation.clamp(0.0, 1.0),
+            _padding: 0.0,
+        };
+        queue.write_buffer(
+            &self.histogram_params_buffer,
+            0,
+            bytemuck::cast_slice(&[histogram_uniform]),
+        );
+    }
+
+    // measures the average luminance of the target, only needed with auto exposure
+    pub fn compute_exposure(&self, command_encoder: &mut wgpu::CommandEncoder) {
+        if !self.auto_exposure {
+            return;
+        }
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Auto Exposure Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, &self.histogram_bind_group, &[]);
+        compute_pass.set_pipeline(&self.histogram_pipeline);
+        compute_pass.dispatch_workgroups(self.width.div_ceil(16), self.height.div_ceil(16), 1);
+        compute_pass.set_pipeline(&self.average_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    pub fn tone_map(&self, command_encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tone Map Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.tone_map_pipeline);
+        render_pass.set_bind_group(0, &self.tone_map_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
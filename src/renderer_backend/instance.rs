@@ -1,9 +1,32 @@
 use cgmath::prelude::*;
 
+// center of the block model, which spans [0, 1] on each axis
+const CENTER_OFFSET: cgmath::Vector3<f32> = cgmath::Vector3::new(0.5, 0.5, 0.5);
+
+// pass an instance is drawn in, in drawing order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderLayer {
+    #[default]
+    Opaque,
+    // alpha tested, e.g. leaves
+    Cutout,
+    // alpha blended, e.g. water
+    Translucent,
+}
+
+impl RenderLayer {
+    pub const ALL: [RenderLayer; 3] = [
+        RenderLayer::Opaque,
+        RenderLayer::Cutout,
+        RenderLayer::Translucent,
+    ];
+}
+
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
     pub scale: f32,
+    pub layer: RenderLayer,
 }
 
 #[repr(C)]
@@ -25,6 +48,17 @@ impl Instance {
         }
     }
 
+    // center and radius of a sphere holding the instance, used for frustum culling.
+    // The model is scaled then rotated around its corner at `position`, see vertex_main in
+    // shader.wgsl, so its center moves with both. The radius is half the cube diagonal
+    pub fn bounding_sphere(&self) -> (cgmath::Vector3<f32>, f32) {
+        (
+            self.position + self.rotation.rotate_vector(CENTER_OFFSET * self.scale),
+            self.scale * f32::sqrt(3.0) / 2.0,
+        )
+    }
+
+    #[allow(dead_code)]
     pub fn default_instance() -> Self {
         Self {
             position: cgmath::Vector3::new(0.0, 0.0, 0.0),
@@ -33,6 +67,7 @@ impl Instance {
                 cgmath::Deg(0.0),
             ),
             scale: 1.0,
+            layer: RenderLayer::Opaque,
         }
     }
 
@@ -64,10 +99,20 @@ impl Instance {
 
                         let scale = 1.0;
 
+                        // a glass roof over a layer of leaves when there is a height
+                        let layer = if dimension < 3 || y + 2 < size {
+                            RenderLayer::Opaque
+                        } else if y + 2 == size {
+                            RenderLayer::Cutout
+                        } else {
+                            RenderLayer::Translucent
+                        };
+
                         Self {
                             position,
                             rotation,
                             scale,
+                            layer,
                         }
                     })
                 })
@@ -75,6 +120,7 @@ impl Instance {
             .collect::<Vec<_>>()
     }
 
+    #[allow(dead_code)]
     pub fn default_buffer(device: &wgpu::Device) -> wgpu::Buffer {
         let instance_data = vec![Instance::default_instance().raw()];
         let buffer_desc = wgpu::util::BufferInitDescriptor {
@@ -0,0 +1,376 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::world::{Block, ChunkPos, World, CHUNK_HEIGHT, CHUNK_SIZE};
+
+pub const MAX_LIGHT: u8 = 15;
+
+const NEIGHBORS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+const DOWN: [i32; 3] = [0, -1, 0];
+
+// both are stored in the same byte of the chunk, sky light in the high nibble
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    // from the top of the world, going down without loss
+    Sky,
+    // emitted by blocks
+    Block,
+}
+
+impl LightChannel {
+    const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+
+    fn shift(self) -> u8 {
+        match self {
+            LightChannel::Sky => 4,
+            LightChannel::Block => 0,
+        }
+    }
+}
+
+pub fn sky_light(light: u8) -> u8 {
+    light >> 4
+}
+
+pub fn block_light(light: u8) -> u8 {
+    light & 0x0f
+}
+
+fn get(world: &World, channel: LightChannel, [x, y, z]: [i32; 3]) -> u8 {
+    (world.get_light(x, y, z) >> channel.shift()) & 0x0f
+}
+
+fn set(world: &mut World, channel: LightChannel, [x, y, z]: [i32; 3], level: u8) {
+    let light = world.get_light(x, y, z) & !(0x0f << channel.shift());
+    world.set_light(x, y, z, light | (level << channel.shift()));
+}
+
+fn add(position: [i32; 3], direction: [i32; 3]) -> [i32; 3] {
+    [
+        position[0] + direction[0],
+        position[1] + direction[1],
+        position[2] + direction[2],
+    ]
+}
+
+// level reached by `level` light going one block in `direction` into `block`
+fn spread(channel: LightChannel, level: u8, direction: [i32; 3], block: Block) -> u8 {
+    let Some(attenuation) = block.light_attenuation() else {
+        return 0;
+    };
+    if channel == LightChannel::Sky && direction == DOWN && level == MAX_LIGHT && attenuation <= 1 {
+        return MAX_LIGHT;
+    }
+    level.saturating_sub(attenuation)
+}
+
+// the chunks whose meshes must be rebuilt, faces sample the light in front of them so
+// the chunks next to a changed border block are dirty too
+#[derive(Default)]
+struct Changes {
+    chunks: HashSet<ChunkPos>,
+}
+
+impl Changes {
+    fn add(&mut self, [x, _, z]: [i32; 3]) {
+        for (dx, dz) in [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)] {
+            self.chunks.insert(ChunkPos::of_block(x + dx, z + dz));
+        }
+    }
+}
+
+// breadth first flood fill from the queued positions, which already hold their level
+fn propagate(
+    world: &mut World,
+    channel: LightChannel,
+    mut queue: VecDeque<[i32; 3]>,
+    changes: &mut Changes,
+) {
+    while let Some(position) = queue.pop_front() {
+        let level = get(world, channel, position);
+        if level <= 1 {
+            continue;
+        }
+        for direction in NEIGHBORS {
+            let neighbor = add(position, direction);
+            let [x, y, z] = neighbor;
+            if !world.is_loaded(x, y, z) {
+                continue;
+            }
+            let new_level = spread(channel, level, direction, world.get_block(x, y, z));
+            if new_level > get(world, channel, neighbor) {
+                set(world, channel, neighbor, new_level);
+                changes.add(neighbor);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+}
+
+// darkens everything lit by the queued positions, their level being given before they
+// were cleared, and returns the brighter positions around the dark area, from which the
+// light must be propagated again
+fn remove(
+    world: &mut World,
+    channel: LightChannel,
+    mut queue: VecDeque<([i32; 3], u8)>,
+    changes: &mut Changes,
+) -> VecDeque<[i32; 3]> {
+    let mut relight = VecDeque::new();
+    while let Some((position, level)) = queue.pop_front() {
+        for direction in NEIGHBORS {
+            let neighbor = add(position, direction);
+            let [x, y, z] = neighbor;
+            if !world.is_loaded(x, y, z) {
+                continue;
+            }
+            let neighbor_level = get(world, channel, neighbor);
+            if neighbor_level == 0 {
+                continue;
+            }
+            let lit_by_position = neighbor_level < level
+                || (channel == LightChannel::Sky
+                    && direction == DOWN
+                    && level == MAX_LIGHT
+                    && neighbor_level == MAX_LIGHT);
+            if lit_by_position {
+                set(world, channel, neighbor, 0);
+                changes.add(neighbor);
+                queue.push_back((neighbor, neighbor_level));
+            } else {
+                relight.push_back(neighbor);
+            }
+        }
+    }
+    relight
+}
+
+// lights every loaded chunk from scratch, light crossing the chunk borders
+pub fn light_world(world: &mut World) {
+    let chunk_positions = world.chunks.keys().copied().collect::<Vec<_>>();
+    let mut sky_queue = VecDeque::new();
+    let mut block_queue = VecDeque::new();
+
+    for chunk_pos in chunk_positions {
+        let (origin_x, origin_z) = chunk_pos.origin();
+        for x in origin_x..origin_x + CHUNK_SIZE {
+            for z in origin_z..origin_z + CHUNK_SIZE {
+                let mut sky = MAX_LIGHT;
+                for y in (0..CHUNK_HEIGHT).rev() {
+                    let block = world.get_block(x, y, z);
+                    sky = spread(LightChannel::Sky, sky, DOWN, block);
+                    let emission = block.light_emission();
+                    world.set_light(x, y, z, sky << 4 | emission);
+                    if sky > 0 {
+                        sky_queue.push_back([x, y, z]);
+                    }
+                    if emission > 0 {
+                        block_queue.push_back([x, y, z]);
+                    }
+                }
+            }
+        }
+    }
+
+    // every chunk is remeshed anyway
+    let mut changes = Changes::default();
+    propagate(world, LightChannel::Sky, sky_queue, &mut changes);
+    propagate(world, LightChannel::Block, block_queue, &mut changes);
+}
+
+// replaces the block and updates the light around it, returns the chunks to remesh
+pub fn set_block(world: &mut World, x: i32, y: i32, z: i32, block: Block) -> HashSet<ChunkPos> {
+    let mut changes = Changes::default();
+    if !world.is_loaded(x, y, z) || world.get_block(x, y, z) == block {
+        return changes.chunks;
+    }
+    world.set_block(x, y, z, block);
+    let position = [x, y, z];
+    changes.add(position);
+
+    for channel in LightChannel::ALL {
+        let level = get(world, channel, position);
+        set(world, channel, position, 0);
+        let mut relight = remove(
+            world,
+            channel,
+            VecDeque::from([(position, level)]),
+            &mut changes,
+        );
+
+        if channel == LightChannel::Block && block.light_emission() > 0 {
+            set(world, channel, position, block.light_emission());
+            relight.push_back(position);
+        }
+        // light can enter the block again from its neighbors, if it lets it through
+        for direction in NEIGHBORS {
+            let neighbor @ [neighbor_x, neighbor_y, neighbor_z] = add(position, direction);
+            if world.is_loaded(neighbor_x, neighbor_y, neighbor_z)
+                && get(world, channel, neighbor) > 0
+            {
+                relight.push_back(neighbor);
+            }
+        }
+        // the sky above the world is not stored
+        if channel == LightChannel::Sky && y == CHUNK_HEIGHT - 1 {
+            let level = spread(channel, MAX_LIGHT, DOWN, block);
+            if level > 0 {
+                set(world, channel, position, level);
+                relight.push_back(position);
+            }
+        }
+        propagate(world, channel, relight, &mut changes);
+    }
+
+    changes
+        .chunks
+        .into_iter()
+        .filter(|chunk_pos| world.chunks.contains_key(chunk_pos))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOF_Y: i32 = 40;
+    const HOLE: [i32; 3] = [8, ROOF_Y, 8];
+
+    fn empty_world(chunk_positions: &[ChunkPos]) -> World {
+        let mut world = World::new();
+        for &chunk_pos in chunk_positions {
+            world.chunks.insert(chunk_pos, Default::default());
+        }
+        world
+    }
+
+    // a single chunk covered by a stone roof with a one block hole, the neighboring chunks
+    // not being loaded so that no light comes from the sides
+    fn roofed_world() -> World {
+        let mut world = empty_world(&[ChunkPos::new(0, 0)]);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                if [x, ROOF_Y, z] != HOLE {
+                    world.set_block(x, ROOF_Y, z, Block::Stone);
+                }
+            }
+        }
+        light_world(&mut world);
+        world
+    }
+
+    fn sky(world: &World, [x, y, z]: [i32; 3]) -> u8 {
+        sky_light(world.get_light(x, y, z))
+    }
+
+    fn block(world: &World, [x, y, z]: [i32; 3]) -> u8 {
+        block_light(world.get_light(x, y, z))
+    }
+
+    #[test]
+    fn sky_light_goes_straight_down_a_column() {
+        let world = roofed_world();
+        for y in 0..CHUNK_HEIGHT {
+            assert_eq!(sky(&world, [8, y, 8]), MAX_LIGHT, "y: {}", y);
+        }
+        // losing a level per block away from the column
+        assert_eq!(sky(&world, [9, 20, 8]), MAX_LIGHT - 1);
+        assert_eq!(sky(&world, [10, 20, 9]), MAX_LIGHT - 3);
+        assert_eq!(sky(&world, [0, 20, 0]), 0);
+        assert_eq!(sky(&world, [0, ROOF_Y + 1, 0]), MAX_LIGHT);
+    }
+
+    #[test]
+    fn block_light_falls_off_by_one_per_step() {
+        let mut world = empty_world(&[ChunkPos::new(0, 0)]);
+        world.set_block(8, 30, 8, Block::Glowstone);
+        light_world(&mut world);
+
+        let emission = Block::Glowstone.light_emission();
+        assert_eq!(block(&world, [8, 30, 8]), emission);
+        for step in 1..=emission as i32 {
+            let expected = emission.saturating_sub(step as u8);
+            assert_eq!(block(&world, [8, 30 - step, 8]), expected, "step: {}", step);
+        }
+        // along the grid, not in a straight line
+        assert_eq!(block(&world, [10, 33, 7]), emission - 6);
+    }
+
+    #[test]
+    fn removing_a_light_source_darkens_its_surroundings() {
+        let mut world = empty_world(&[ChunkPos::new(0, 0)]);
+        world.set_block(8, 30, 8, Block::Glowstone);
+        world.set_block(4, 30, 8, Block::Glowstone);
+        light_world(&mut world);
+        assert_eq!(block(&world, [10, 30, 8]), 12);
+
+        let changed = set_block(&mut world, 8, 30, 8, Block::Air);
+        assert!(changed.contains(&ChunkPos::new(0, 0)));
+        // only the light of the remaining source is left
+        assert_eq!(block(&world, [8, 30, 8]), 10);
+        assert_eq!(block(&world, [10, 30, 8]), 8);
+        assert_eq!(block(&world, [4, 30, 8]), 14);
+        assert_eq!(block(&world, [13, 30, 8]), 5);
+    }
+
+    #[test]
+    fn removing_a_roof_block_lets_the_sky_light_down() {
+        let mut world = roofed_world();
+        set_block(&mut world, 0, ROOF_Y, 0, Block::Air);
+        for y in 0..ROOF_Y {
+            assert_eq!(sky(&world, [0, y, 0]), MAX_LIGHT, "y: {}", y);
+        }
+        assert_eq!(sky(&world, [1, 20, 1]), MAX_LIGHT - 2);
+        // still lit by the other hole
+        assert_eq!(sky(&world, [8, 0, 8]), MAX_LIGHT);
+    }
+
+    #[test]
+    fn placing_an_opaque_block_stops_the_light() {
+        let mut world = roofed_world();
+        set_block(&mut world, HOLE[0], HOLE[1], HOLE[2], Block::Stone);
+        for y in 0..ROOF_Y {
+            for (x, z) in [(8, 8), (9, 8), (0, 0)] {
+                assert_eq!(sky(&world, [x, y, z]), 0, "{} {} {}", x, y, z);
+            }
+        }
+        assert_eq!(sky(&world, [8, ROOF_Y, 8]), 0);
+        assert_eq!(sky(&world, [8, ROOF_Y + 1, 8]), MAX_LIGHT);
+
+        let mut world = empty_world(&[ChunkPos::new(0, 0)]);
+        world.set_block(8, 30, 8, Block::Glowstone);
+        light_world(&mut world);
+        assert_eq!(block(&world, [10, 30, 8]), 12);
+        set_block(&mut world, 9, 30, 8, Block::Stone);
+        assert_eq!(block(&world, [9, 30, 8]), 0);
+        // going around the stone
+        assert_eq!(block(&world, [10, 30, 8]), 10);
+    }
+
+    #[test]
+    fn light_crosses_chunk_borders() {
+        let mut world = empty_world(&[ChunkPos::new(0, 0), ChunkPos::new(1, 0)]);
+        world.set_block(CHUNK_SIZE - 1, 30, 8, Block::Glowstone);
+        light_world(&mut world);
+        assert_eq!(block(&world, [CHUNK_SIZE, 30, 8]), 13);
+        assert_eq!(block(&world, [CHUNK_SIZE + 2, 30, 8]), 11);
+
+        let changed = set_block(&mut world, CHUNK_SIZE - 1, 30, 8, Block::Air);
+        assert_eq!(
+            changed,
+            HashSet::from([ChunkPos::new(0, 0), ChunkPos::new(1, 0)])
+        );
+        assert_eq!(block(&world, [CHUNK_SIZE, 30, 8]), 0);
+        assert_eq!(block(&world, [CHUNK_SIZE + 2, 30, 8]), 0);
+
+        // placed in the other chunk, lighting back the first one
+        set_block(&mut world, CHUNK_SIZE, 30, 8, Block::Glowstone);
+        assert_eq!(block(&world, [CHUNK_SIZE - 3, 30, 8]), 11);
+    }
+}
@@ -0,0 +1,385 @@
+use wgpu::util::DeviceExt;
+
+use super::{
+    light,
+    model::Vertex,
+    world::{self, ChunkPos, World, CHUNK_HEIGHT, CHUNK_SIZE},
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoxelVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    // see pack_vertex_light
+    pub light: u32,
+}
+
+impl Vertex for VoxelVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<VoxelVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        }
+    }
+}
+
+struct Face {
+    normal: [i32; 3],
+    // edges of the quad, u x v = normal so that the corners are counter clockwise
+    // when seen from outside
+    u: [i32; 3],
+    v: [i32; 3],
+}
+
+const FACES: [Face; 6] = [
+    Face {
+        normal: [1, 0, 0],
+        u: [0, 1, 0],
+        v: [0, 0, 1],
+    },
+    Face {
+        normal: [-1, 0, 0],
+        u: [0, 0, 1],
+        v: [0, 1, 0],
+    },
+    Face {
+        normal: [0, 1, 0],
+        u: [0, 0, 1],
+        v: [1, 0, 0],
+    },
+    Face {
+        normal: [0, -1, 0],
+        u: [1, 0, 0],
+        v: [0, 0, 1],
+    },
+    Face {
+        normal: [0, 0, 1],
+        u: [1, 0, 0],
+        v: [0, 1, 0],
+    },
+    Face {
+        normal: [0, 0, -1],
+        u: [0, 1, 0],
+        v: [1, 0, 0],
+    },
+];
+
+// (u, v) offsets of the quad corners, in winding order
+const CORNERS: [[i32; 2]; 4] = [[0, 0], [1, 0], [1, 1], [0, 1]];
+
+// ambient occlusion in bits 0-1, sky light in bits 4-7 and block light in bits 8-11
+pub fn pack_vertex_light(ao: u8, sky_light: u8, block_light: u8) -> u32 {
+    ao as u32 | (sky_light as u32) << 4 | (block_light as u32) << 8
+}
+
+// classic voxel ambient occlusion: the two blocks sharing an edge with the corner and
+// the one sharing only the corner, in the layer in front of the face.
+// Two sides fully close the corner whatever the diagonal block is.
+pub fn vertex_ao(side1: bool, side2: bool, corner: bool) -> u8 {
+    if side1 && side2 {
+        0
+    } else {
+        3 - (side1 as u8 + side2 as u8 + corner as u8)
+    }
+}
+
+// splits the quad along the diagonal between its brightest corners, otherwise the
+// interpolation makes the occlusion anisotropic
+pub fn is_quad_flipped(ao: [u8; 4]) -> bool {
+    ao[0] + ao[2] < ao[1] + ao[3]
+}
+
+fn offset(position: [i32; 3], direction: [i32; 3], scale: i32) -> [i32; 3] {
+    [
+        position[0] + direction[0] * scale,
+        position[1] + direction[1] * scale,
+        position[2] + direction[2] * scale,
+    ]
+}
+
+// occlusion and smooth light of the four corners of the face of `position` looking
+// toward `face.normal`, the light being averaged over the transparent blocks touching
+// the corner in front of the face
+fn face_light(world: &World, position: [i32; 3], face: &Face) -> [(u8, u32); 4] {
+    let is_opaque = |[x, y, z]: [i32; 3]| world.get_block(x, y, z).is_opaque();
+    let get_light = |[x, y, z]: [i32; 3]| world.get_light(x, y, z);
+    let front = offset(position, face.normal, 1);
+    CORNERS.map(|[a, b]| {
+        let side1 = offset(front, face.u, a * 2 - 1);
+        let side2 = offset(front, face.v, b * 2 - 1);
+        let corner = offset(side1, face.v, b * 2 - 1);
+        let (side1_opaque, side2_opaque) = (is_opaque(side1), is_opaque(side2));
+        let ao = vertex_ao(side1_opaque, side2_opaque, is_opaque(corner));
+
+        let mut samples = [get_light(front), 0, 0, 0];
+        let mut count = 1;
+        let mut add_sample = |block: [i32; 3]| {
+            samples[count] = get_light(block);
+            count += 1;
+        };
+        if !side1_opaque {
+            add_sample(side1);
+        }
+        if !side2_opaque {
+            add_sample(side2);
+        }
+        // the corner block is cut off from the vertex when both sides are opaque
+        if (!side1_opaque || !side2_opaque) && !is_opaque(corner) {
+            add_sample(corner);
+        }
+        let average = |channel: fn(u8) -> u8| {
+            let sum = samples[..count]
+                .iter()
+                .map(|&l| channel(l) as u32)
+                .sum::<u32>();
+            ((sum as f32 / count as f32).round()) as u8
+        };
+        let packed = pack_vertex_light(ao, average(light::sky_light), average(light::block_light));
+        (ao, packed)
+    })
+}
+
+#[derive(Default)]
+pub struct MeshData {
+    pub vertices: Vec<VoxelVertex>,
+    pub indices: Vec<u32>,
+}
+
+// visible faces of the chunk in world coordinates, neighbor chunks included in the
+// culling and the occlusion
+pub fn build_chunk_mesh(world: &World, chunk_pos: ChunkPos) -> MeshData {
+    let mut mesh = MeshData::default();
+    let Some(chunk) = world.chunks.get(&chunk_pos) else {
+        return mesh;
+    };
+    let (origin_x, origin_z) = chunk_pos.origin();
+
+    for y in 0..CHUNK_HEIGHT {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                if chunk.get(x, y, z) == world::Block::Air {
+                    continue;
+                }
+                let position = [origin_x + x, y, origin_z + z];
+                for face in &FACES {
+                    let [front_x, front_y, front_z] = offset(position, face.normal, 1);
+                    if world.get_block(front_x, front_y, front_z).is_opaque() {
+                        continue;
+                    }
+                    add_face(&mut mesh, position, face, face_light(world, position, face));
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+fn add_face(mesh: &mut MeshData, position: [i32; 3], face: &Face, corners: [(u8, u32); 4]) {
+    // the face lies on the far side of the block along positive normals
+    let origin = offset(position, face.normal.map(|n| n.max(0)), 1);
+    let first = mesh.vertices.len() as u32;
+    for ([a, b], (_, light)) in CORNERS.iter().zip(corners) {
+        let corner = offset(offset(origin, face.u, *a), face.v, *b);
+        mesh.vertices.push(VoxelVertex {
+            position: corner.map(|c| c as f32),
+            tex_coords: [*a as f32, *b as f32],
+            normal: face.normal.map(|n| n as f32),
+            light,
+        });
+    }
+    let quad: [u32; 6] = if is_quad_flipped(corners.map(|(ao, _)| ao)) {
+        [1, 2, 3, 1, 3, 0]
+    } else {
+        [0, 1, 2, 0, 2, 3]
+    };
+    mesh.indices.extend(quad.map(|index| first + index));
+}
+
+pub struct ChunkMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_elements: u32,
+}
+
+impl ChunkMesh {
+    // None when the chunk has no visible face
+    pub fn new(device: &wgpu::Device, mesh: &MeshData, label: &str) -> Option<Self> {
+        if mesh.indices.is_empty() {
+            return None;
+        }
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Vertex Buffer", label)),
+            contents: bytemuck::cast_slice(&mesh.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Index Buffer", label)),
+            contents: bytemuck::cast_slice(&mesh.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Some(Self {
+            vertex_buffer,
+            index_buffer,
+            num_elements: mesh.indices.len() as u32,
+        })
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_elements, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::Block;
+
+    fn face_count(mesh: &MeshData) -> usize {
+        assert_eq!(mesh.vertices.len() % 4, 0);
+        assert_eq!(mesh.indices.len(), mesh.vertices.len() / 4 * 6);
+        mesh.vertices.len() / 4
+    }
+
+    fn world_with(blocks: &[[i32; 3]]) -> World {
+        let mut world = World::new();
+        for &[x, y, z] in blocks {
+            world.set_block(x, y, z, Block::Stone);
+        }
+        world
+    }
+
+    #[test]
+    fn vertex_ao_counts_the_open_neighbors() {
+        for side1 in [false, true] {
+            for side2 in [false, true] {
+                for corner in [false, true] {
+                    let expected = match (side1, side2, corner) {
+                        (true, true, _) => 0,
+                        _ => 3 - (side1 as u8 + side2 as u8 + corner as u8),
+                    };
+                    assert_eq!(
+                        vertex_ao(side1, side2, corner),
+                        expected,
+                        "side1: {}, side2: {}, corner: {}",
+                        side1,
+                        side2,
+                        corner
+                    );
+                }
+            }
+        }
+        assert_eq!(vertex_ao(false, false, false), 3);
+        assert_eq!(vertex_ao(false, false, true), 2);
+        assert_eq!(vertex_ao(true, false, true), 1);
+        assert_eq!(vertex_ao(true, true, false), 0);
+    }
+
+    #[test]
+    fn quad_is_flipped_toward_the_brightest_diagonal() {
+        assert!(!is_quad_flipped([3, 3, 3, 3]));
+        assert!(!is_quad_flipped([3, 0, 3, 0]));
+        assert!(is_quad_flipped([0, 3, 0, 3]));
+        assert!(is_quad_flipped([0, 3, 3, 3]));
+        assert!(!is_quad_flipped([3, 3, 3, 0]));
+    }
+
+    #[test]
+    fn single_block_has_six_faces() {
+        let world = world_with(&[[2, 3, 4]]);
+        let mesh = build_chunk_mesh(&world, ChunkPos::new(0, 0));
+        assert_eq!(face_count(&mesh), 6);
+        // nothing occludes the corners
+        assert!(mesh.vertices.iter().all(|vertex| vertex.light & 3 == 3));
+    }
+
+    #[test]
+    fn faces_are_counter_clockwise_from_outside() {
+        let world = world_with(&[[2, 3, 4]]);
+        let mesh = build_chunk_mesh(&world, ChunkPos::new(0, 0));
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+            let edge1 = cgmath::Vector3::from(b.position) - cgmath::Vector3::from(a.position);
+            let edge2 = cgmath::Vector3::from(c.position) - cgmath::Vector3::from(a.position);
+            let normal = cgmath::Vector3::from(a.normal);
+            assert!(cgmath::InnerSpace::dot(edge1.cross(edge2), normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn block_in_front_of_a_face_occludes_its_corners() {
+        // above the top face of the first block, along its +x edge
+        let world = world_with(&[[2, 3, 4], [3, 4, 4]]);
+        let mesh = build_chunk_mesh(&world, ChunkPos::new(0, 0));
+        let top_corners = mesh
+            .vertices
+            .iter()
+            .filter(|vertex| vertex.normal == [0.0, 1.0, 0.0] && vertex.position[1] == 4.0);
+        for vertex in top_corners {
+            let expected = if vertex.position[0] == 3.0 { 2 } else { 3 };
+            assert_eq!(vertex.light & 3, expected, "{:?}", vertex.position);
+        }
+    }
+
+    #[test]
+    fn faces_between_opaque_blocks_are_culled() {
+        let world = world_with(&[[2, 3, 4], [3, 3, 4]]);
+        let mesh = build_chunk_mesh(&world, ChunkPos::new(0, 0));
+        assert_eq!(face_count(&mesh), 10);
+
+        let world = world_with(&[[2, 3, 4], [3, 3, 4], [2, 4, 4], [2, 3, 5]]);
+        let mesh = build_chunk_mesh(&world, ChunkPos::new(0, 0));
+        assert_eq!(face_count(&mesh), 4 * 6 - 3 * 2);
+    }
+
+    #[test]
+    fn neighbor_chunks_cull_the_border_faces() {
+        let last = CHUNK_SIZE - 1;
+        let world = world_with(&[[last, 3, 4], [CHUNK_SIZE, 3, 4]]);
+        for chunk_pos in [ChunkPos::new(0, 0), ChunkPos::new(1, 0)] {
+            let mesh = build_chunk_mesh(&world, chunk_pos);
+            assert_eq!(face_count(&mesh), 5);
+        }
+
+        // across negative coordinates too
+        let world = world_with(&[[0, 3, 0], [0, 3, -1]]);
+        for chunk_pos in [ChunkPos::new(0, 0), ChunkPos::new(0, -1)] {
+            let mesh = build_chunk_mesh(&world, chunk_pos);
+            assert_eq!(face_count(&mesh), 5);
+        }
+    }
+
+    #[test]
+    fn missing_chunk_has_no_mesh() {
+        let world = world_with(&[[2, 3, 4]]);
+        let mesh = build_chunk_mesh(&world, ChunkPos::new(5, 5));
+        assert_eq!(face_count(&mesh), 0);
+    }
+}
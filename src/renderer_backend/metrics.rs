@@ -0,0 +1,255 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// frames kept for the averages and the frame graph
+pub const HISTORY_LENGTH: usize = 128;
+
+// parts of a frame timed on the CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Update,
+    Culling,
+    Upload,
+    Encode,
+    Submit,
+    Present,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Update,
+        Phase::Culling,
+        Phase::Upload,
+        Phase::Encode,
+        Phase::Submit,
+        Phase::Present,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Update => "update",
+            Phase::Culling => "culling",
+            Phase::Upload => "upload",
+            Phase::Encode => "encode",
+            Phase::Submit => "submit",
+            Phase::Present => "present",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    pub frame_time: Duration,
+    // indexed by Phase
+    pub phases: [Duration; Phase::ALL.len()],
+    // milliseconds per timed pass, from a few frames earlier, empty for the frames without
+    // a completed readback
+    pub gpu_passes: Vec<(&'static str, f32)>,
+    pub draw_calls: u32,
+    pub triangles: u64,
+    pub visible_instances: u32,
+}
+
+impl FrameStats {
+    pub fn gpu_time(&self) -> f32 {
+        self.gpu_passes.iter().map(|(_, ms)| ms).sum()
+    }
+}
+
+pub struct Metrics {
+    // filled during the frame
+    pub current: FrameStats,
+    history: VecDeque<FrameStats>,
+    frame_count: u64,
+    // every timed pass, the columns of the CSV log
+    gpu_labels: &'static [&'static str],
+    csv: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl Metrics {
+    pub fn new(gpu_labels: &'static [&'static str]) -> Self {
+        Self {
+            current: FrameStats::default(),
+            history: VecDeque::with_capacity(HISTORY_LENGTH),
+            frame_count: 0,
+            gpu_labels,
+            csv: None,
+        }
+    }
+
+    // sums up when a phase runs several times in a frame, e.g. the uploads
+    pub fn add_phase(&mut self, phase: Phase, duration: Duration) {
+        self.current.phases[phase as usize] += duration;
+    }
+
+    pub fn add_draw(&mut self, triangles: u64) {
+        self.current.draw_calls += 1;
+        self.current.triangles += triangles;
+    }
+
+    pub fn set_visible_instances(&mut self, visible_instances: u32) {
+        self.current.visible_instances = visible_instances;
+    }
+
+    pub fn history(&self) -> &VecDeque<FrameStats> {
+        &self.history
+    }
+
+    // a failed CSV write stops the log, the error is only returned by that frame
+    pub fn end_frame(
+        &mut self,
+        frame_time: Duration,
+        gpu_passes: &[(&'static str, f32)],
+    ) -> Result<(), String> {
+        let mut stats = std::mem::take(&mut self.current);
+        stats.frame_time = frame_time;
+        stats.gpu_passes = gpu_passes.to_vec();
+
+        let mut result = Ok(());
+        if let Some((path, writer)) = &mut self.csv {
+            let row = Self::csv_row(self.frame_count, &stats, self.gpu_labels);
+            if let Err(e) = writeln!(writer, "{}", row) {
+                result = Err(format!("cannot write {}: {}", path.display(), e));
+                self.csv = None;
+            }
+        }
+
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(stats);
+        self.frame_count += 1;
+        result
+    }
+
+    // averages over the history, the GPU timings over the frames that have one
+    pub fn average(&self) -> FrameStats {
+        let mut average = FrameStats::default();
+        let count = self.history.len().max(1) as u32;
+        let mut gpu_counts: Vec<u32> = Vec::new();
+        for stats in &self.history {
+            average.frame_time += stats.frame_time / count;
+            for (total, phase) in average.phases.iter_mut().zip(stats.phases) {
+                *total += phase / count;
+            }
+            for &(label, ms) in &stats.gpu_passes {
+                match average.gpu_passes.iter().position(|(l, _)| *l == label) {
+                    Some(index) => {
+                        average.gpu_passes[index].1 += ms;
+                        gpu_counts[index] += 1;
+                    }
+                    None => {
+                        average.gpu_passes.push((label, ms));
+                        gpu_counts.push(1);
+                    }
+                }
+            }
+        }
+        for ((_, total), count) in average.gpu_passes.iter_mut().zip(gpu_counts) {
+            *total /= count as f32;
+        }
+        // the counters of the last frame
+        if let Some(last) = self.history.back() {
+            average.draw_calls = last.draw_calls;
+            average.triangles = last.triangles;
+            average.visible_instances = last.visible_instances;
+        }
+        average
+    }
+
+    pub fn summary(&self) -> String {
+        let average = self.average();
+        let frame_ms = average.frame_time.as_secs_f32() * 1000.0;
+        let mut lines = vec![format!(
+            "{:.1} fps  {:.2} ms",
+            1000.0 / frame_ms.max(0.001),
+            frame_ms
+        )];
+        lines.push(
+            Phase::ALL
+                .iter()
+                .map(|&phase| {
+                    let ms = average.phases[phase as usize].as_secs_f32() * 1000.0;
+                    format!("{} {:.2}", phase.name(), ms)
+                })
+                .collect::<Vec<_>>()
+                .join("  "),
+        );
+        if !average.gpu_passes.is_empty() {
+            lines.push(format!(
+                "gpu {:.2} ms: {}",
+                average.gpu_time(),
+                average
+                    .gpu_passes
+                    .iter()
+                    .map(|(label, ms)| format!("{} {:.2}", label, ms))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+            ));
+        }
+        lines.push(format!(
+            "{} draw calls  {} triangles  {} visible instances",
+            average.draw_calls, average.triangles, average.visible_instances
+        ));
+        lines.join("\n")
+    }
+
+    pub fn csv_path(&self) -> Option<&Path> {
+        self.csv.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    // one row per frame from now on, times in milliseconds
+    pub fn start_csv(&mut self, path: &Path) -> io::Result<()> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let mut header = vec!["frame".to_string(), "frame_ms".to_string()];
+        header.extend(Phase::ALL.map(|phase| format!("{}_ms", phase.name())));
+        header.extend(
+            self.gpu_labels
+                .iter()
+                .map(|label| format!("gpu_{}_ms", label)),
+        );
+        header.extend(["draw_calls", "triangles", "visible_instances"].map(String::from));
+        writeln!(writer, "{}", header.join(","))?;
+
+        self.csv = Some((path.to_path_buf(), writer));
+        Ok(())
+    }
+
+    pub fn stop_csv(&mut self) -> Result<(), String> {
+        match self.csv.take() {
+            Some((path, mut writer)) => writer
+                .flush()
+                .map_err(|e| format!("cannot write {}: {}", path.display(), e)),
+            None => Ok(()),
+        }
+    }
+
+    fn csv_row(frame: u64, stats: &FrameStats, gpu_labels: &[&str]) -> String {
+        let ms = |duration: Duration| format!("{:.4}", duration.as_secs_f64() * 1000.0);
+        let mut row = vec![frame.to_string(), ms(stats.frame_time)];
+        row.extend(stats.phases.map(ms));
+        // empty when the pass was not timed in that frame
+        row.extend(gpu_labels.iter().map(|label| {
+            stats
+                .gpu_passes
+                .iter()
+                .find(|(l, _)| l == label)
+                .map_or(String::new(), |(_, ms)| format!("{:.4}", ms))
+        }));
+        row.extend([
+            stats.draw_calls.to_string(),
+            stats.triangles.to_string(),
+            stats.visible_instances.to_string(),
+        ]);
+        row.join(",")
+    }
+}
@@ -0,0 +1,77 @@
+use super::{bind_group, bind_group_layout, pipeline};
+
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// fills every level below 0 by blitting the previous one with a linear filter,
+// the texture needs RENDER_ATTACHMENT usage
+pub fn generate_mipmaps(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+    let layout = {
+        let mut builder = bind_group_layout::Builder::new(device);
+        builder.add_texture();
+        builder.build("Mipmap Bind Group Layout")
+    };
+
+    let blit_pipeline = {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_fullscreen_shader("shaders/blit.wgsl", "fragment_main");
+        builder.set_pixel_format(texture.format());
+        builder.add_bind_group_layout(&layout);
+        builder.build_pipeline("Mipmap Pipeline")
+    };
+
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Mipmap Sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let views = (0..texture.mip_level_count())
+        .map(|mip_level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Mip View"),
+                base_mip_level: mip_level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Mipmap Encoder"),
+    });
+
+    for target_level in 1..views.len() {
+        let bind_group = {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&layout);
+            builder.add_texture(&views[target_level - 1], &sampler);
+            builder.build("Mipmap Bind Group")
+        };
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Mipmap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &views[target_level],
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&blit_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    queue.submit(std::iter::once(command_encoder.finish()));
+}
@@ -1,7 +1,33 @@
 pub mod bind_group;
 pub mod bind_group_layout;
+pub mod bloom;
 pub mod camera;
+pub mod camera_path;
+pub mod capture;
+pub mod compute_pipeline;
+pub mod console;
+pub mod cubemap;
+pub mod debug_draw;
+pub mod debug_ui;
+pub mod debug_view;
+pub mod environment;
+pub mod frame_graph;
+pub mod gpu_timer;
+pub mod hdr;
 pub mod instance;
+pub mod light;
+pub mod mesher;
+pub mod metrics;
+pub mod mipmap;
 pub mod model;
 pub mod pipeline;
+pub mod post_process;
+pub mod preprocessor;
+pub mod reflection;
+pub mod screenshot;
+pub mod sky;
+pub mod text;
 pub mod texture;
+pub mod time_of_day;
+pub mod transparency;
+pub mod world;
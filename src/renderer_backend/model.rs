@@ -1,6 +1,6 @@
 use wgpu::util::DeviceExt;
 
-use super::{bind_group, instance, texture};
+use super::{instance, texture};
 
 pub trait Vertex {
     fn desc() -> wgpu::VertexBufferLayout<'static>;
@@ -45,9 +45,11 @@ const MODEL_DIR: &str = "models";
 
 pub struct Model {
     pub meshes: Vec<Mesh>,
+    #[allow(dead_code)]
     pub materials: Vec<Material>,
 }
 
+#[allow(dead_code)]
 pub struct Material {
     pub name: String,
     pub diffuse_texture: texture::Texture,
@@ -55,10 +57,12 @@ pub struct Material {
 }
 
 pub struct Mesh {
+    #[allow(dead_code)]
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
+    #[allow(dead_code)]
     pub material: usize,
 }
 
@@ -114,12 +118,11 @@ impl Model {
                     texture::Texture::from_image(&img, device, queue, None, Some(file_name))
                 };
 
-                let bind_group = {
-                    let mut builder = bind_group::Builder::new(device);
-                    builder.set_layout(&texture_layout);
-                    builder.add_texture(&diffuse_texture.view, &diffuse_texture.sampler);
-                    builder.build(&format!("{} bind group", file_name))
-                };
+                let bind_group = diffuse_texture.create_bind_group(
+                    device,
+                    &texture_layout,
+                    &format!("{} bind group", file_name),
+                );
 
                 Material {
                     name: m.name,
@@ -195,6 +198,7 @@ impl Model {
 }
 
 pub trait DrawModel<'a> {
+    #[allow(dead_code)]
     fn draw_mesh(&mut self, mesh: &'a Mesh, device: &wgpu::Device);
     fn draw_mesh_instanced(
         &mut self,
@@ -1,14 +1,63 @@
-use std::env::current_dir;
-use std::fs;
+use super::{preprocessor::Preprocessor, reflection::ShaderReflection, texture};
 
-use super::texture;
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendPreset {
+    Replace,
+    Alpha,
+    Additive,
+    Premultiplied,
+}
+
+impl BlendPreset {
+    pub fn state(self) -> wgpu::BlendState {
+        match self {
+            BlendPreset::Replace => wgpu::BlendState::REPLACE,
+            BlendPreset::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendPreset::Premultiplied => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendPreset::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+        }
+    }
+}
+
+fn default_depth_stencil() -> wgpu::DepthStencilState {
+    wgpu::DepthStencilState {
+        format: texture::Texture::DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    }
+}
 
 pub struct Builder<'a> {
     shader_filename: String,
     vertex_entry: String,
     fragment_entry: String,
+    vertex_only: bool,
+    preprocessor: Preprocessor,
     pixel_format: wgpu::TextureFormat,
+    blend: Option<wgpu::BlendState>,
+    color_targets: Vec<Option<wgpu::ColorTargetState>>,
+    topology: wgpu::PrimitiveTopology,
+    strip_index_format: wgpu::IndexFormat,
     front_face: wgpu::FrontFace,
+    cull_mode: Option<wgpu::Face>,
+    polygon_mode: wgpu::PolygonMode,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    sample_count: u32,
+    alpha_to_coverage: bool,
     vertex_buffer_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
     bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
     device: &'a wgpu::Device,
@@ -20,17 +69,28 @@ impl<'a> Builder<'a> {
             shader_filename: "dummy".to_string(),
             vertex_entry: "vertex_main".to_string(),
             fragment_entry: "fragment_main".to_string(),
+            vertex_only: false,
+            preprocessor: Preprocessor::new(),
             pixel_format: wgpu::TextureFormat::Bgra8Unorm,
+            blend: Some(wgpu::BlendState::REPLACE),
+            color_targets: Vec::new(),
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: wgpu::IndexFormat::Uint32,
             front_face: wgpu::FrontFace::Cw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            depth_stencil: Some(default_depth_stencil()),
+            sample_count: 1,
+            alpha_to_coverage: false,
             vertex_buffer_layouts: Vec::new(),
             bind_group_layouts: Vec::new(),
             device,
         }
     }
 
+    // back to the state of `new`, done after every build
     pub fn reset(&mut self) {
-        self.vertex_buffer_layouts.clear();
-        self.bind_group_layouts.clear();
+        *self = Self::new(self.device);
     }
 
     pub fn add_vertex_buffer_layout(&mut self, layout: wgpu::VertexBufferLayout<'static>) {
@@ -52,28 +112,153 @@ impl<'a> Builder<'a> {
         self.fragment_entry = fragment_entry.to_string();
     }
 
+    // single triangle from common/fullscreen.wgsl, drawn with `draw(0..3, 0..1)`
+    pub fn set_fullscreen_shader(&mut self, shader_filename: &str, fragment_entry: &str) {
+        self.set_shader_module(shader_filename, "fullscreen_vertex", fragment_entry);
+        self.set_cull_mode(None);
+        self.disable_depth();
+    }
+
+    // no fragment stage, e.g. for depth-only passes
+    #[allow(dead_code)]
+    pub fn set_vertex_only(&mut self, vertex_only: bool) {
+        self.vertex_only = vertex_only;
+    }
+
+    pub fn add_define(&mut self, name: &str, value: &str) {
+        self.preprocessor.define(name, value);
+    }
+
     pub fn set_pixel_format(&mut self, pixel_format: wgpu::TextureFormat) {
         self.pixel_format = pixel_format;
     }
 
+    pub fn set_blend(&mut self, blend: Option<wgpu::BlendState>) {
+        self.blend = blend;
+    }
+
+    pub fn set_blend_preset(&mut self, preset: BlendPreset) {
+        self.blend = Some(preset.state());
+    }
+
+    // once a target is added, pixel format and blend are ignored in favor of the target list
+    pub fn add_color_target(
+        &mut self,
+        format: wgpu::TextureFormat,
+        blend: Option<wgpu::BlendState>,
+        write_mask: wgpu::ColorWrites,
+    ) {
+        self.color_targets.push(Some(wgpu::ColorTargetState {
+            format,
+            blend,
+            write_mask,
+        }));
+    }
+
+    pub fn set_topology(&mut self, topology: wgpu::PrimitiveTopology) {
+        self.topology = topology;
+    }
+
+    // format of the index buffers drawn with a strip topology, ignored by the others
+    #[allow(dead_code)]
+    pub fn set_strip_index_format(&mut self, format: wgpu::IndexFormat) {
+        self.strip_index_format = format;
+    }
+
     pub fn set_front_face(&mut self, front_face: wgpu::FrontFace) {
         self.front_face = front_face;
     }
 
-    pub fn build_pipeline(&mut self, label: &str) -> wgpu::RenderPipeline {
-        let mut filepath = current_dir().unwrap();
-        filepath.push("src/");
-        filepath.push(&self.shader_filename);
-        let filepath = filepath.into_os_string().into_string().unwrap();
-        let source_code = fs::read_to_string(&filepath)
-            .expect(&format!("Can't read the source code of {}", filepath));
-
-        let shader_module_descriptor = wgpu::ShaderModuleDescriptor {
-            label: Some(&filepath),
-            source: wgpu::ShaderSource::Wgsl(source_code.into()),
+    pub fn set_cull_mode(&mut self, cull_mode: Option<wgpu::Face>) {
+        self.cull_mode = cull_mode;
+    }
+
+    // falls back to Fill when the device lacks the matching POLYGON_MODE_* feature, see
+    // supported_polygon_mode
+    pub fn set_polygon_mode(&mut self, polygon_mode: wgpu::PolygonMode) {
+        self.polygon_mode = polygon_mode;
+    }
+
+    fn depth_stencil_mut(&mut self) -> &mut wgpu::DepthStencilState {
+        self.depth_stencil.get_or_insert_with(default_depth_stencil)
+    }
+
+    #[allow(dead_code)]
+    pub fn set_depth_format(&mut self, format: wgpu::TextureFormat) {
+        self.depth_stencil_mut().format = format;
+    }
+
+    pub fn set_depth_compare(&mut self, depth_compare: wgpu::CompareFunction) {
+        self.depth_stencil_mut().depth_compare = depth_compare;
+    }
+
+    pub fn set_depth_write(&mut self, depth_write_enabled: bool) {
+        self.depth_stencil_mut().depth_write_enabled = depth_write_enabled;
+    }
+
+    pub fn set_depth_bias(&mut self, constant: i32, slope_scale: f32, clamp: f32) {
+        self.depth_stencil_mut().bias = wgpu::DepthBiasState {
+            constant,
+            slope_scale,
+            clamp,
         };
-        let shader_module = self.device.create_shader_module(shader_module_descriptor);
+    }
 
+    // needs a depth format with a stencil aspect, see set_depth_format
+    #[allow(dead_code)]
+    pub fn set_stencil(&mut self, stencil: wgpu::StencilState) {
+        self.depth_stencil_mut().stencil = stencil;
+    }
+
+    pub fn disable_depth(&mut self) {
+        self.depth_stencil = None;
+    }
+
+    // must match the sample count of every attachment the pipeline renders to
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count;
+    }
+
+    // only has an effect when multisampling
+    pub fn set_alpha_to_coverage(&mut self, alpha_to_coverage: bool) {
+        self.alpha_to_coverage = alpha_to_coverage;
+    }
+
+    pub fn build_pipeline(&mut self, label: &str) -> wgpu::RenderPipeline {
+        self.try_build_pipeline(label)
+            .unwrap_or_else(|message| panic!("{}", message))
+    }
+
+    // errors in the shader are returned instead of panicking, e.g. when reloading it
+    pub fn try_build_pipeline(&mut self, label: &str) -> Result<wgpu::RenderPipeline, String> {
+        let render_pipeline = self.create_pipeline(label);
+        self.reset();
+        render_pipeline
+    }
+
+    // the shader as preprocessed with the defines of this builder, e.g. to reflect the bind
+    // group layouts from the permutations a pipeline is built with
+    pub fn reflect(&self) -> Result<ShaderReflection, String> {
+        ShaderReflection::try_new(&self.preprocessor.try_load(&self.shader_filename)?)
+    }
+
+    fn create_pipeline(&self, label: &str) -> Result<wgpu::RenderPipeline, String> {
+        let shader = self.preprocessor.try_load(&self.shader_filename)?;
+        let reflection = ShaderReflection::try_new(&shader)?;
+        if let Err(errors) =
+            reflection.validate_vertex_buffers(&self.vertex_entry, &self.vertex_buffer_layouts)
+        {
+            return Err(format!(
+                "{}: vertex buffers don't match the inputs of `{}`:\n    {}",
+                self.shader_filename,
+                self.vertex_entry,
+                errors.join("\n    ")
+            ));
+        }
+        let shader_module = shader.try_create_shader_module(self.device)?;
+
+        // e.g. bind group layouts not matching the shader
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
         let pipeline_layout_descriptor = wgpu::PipelineLayoutDescriptor {
             label: Some(label),
             bind_group_layouts: &self.bind_group_layouts,
@@ -83,14 +268,27 @@ impl<'a> Builder<'a> {
             .device
             .create_pipeline_layout(&pipeline_layout_descriptor);
 
-        let render_targets = [Some(wgpu::ColorTargetState {
-            format: self.pixel_format,
-            blend: Some(wgpu::BlendState::REPLACE),
-            write_mask: wgpu::ColorWrites::ALL,
-        })];
+        let render_targets = if self.color_targets.is_empty() {
+            vec![Some(wgpu::ColorTargetState {
+                format: self.pixel_format,
+                blend: self.blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })]
+        } else {
+            self.color_targets.clone()
+        };
+
+        let fragment = (!self.vertex_only).then(|| wgpu::FragmentState {
+            module: &shader_module,
+            entry_point: &self.fragment_entry,
+            targets: &render_targets,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        });
+
+        let strip_index_format = self.topology.is_strip().then_some(self.strip_index_format);
 
         let render_pipeline_descriptor = wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
+            label: Some(label),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &shader_module,
@@ -99,31 +297,20 @@ impl<'a> Builder<'a> {
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
+                topology: self.topology,
+                strip_index_format,
                 front_face: self.front_face,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
+                cull_mode: self.cull_mode,
+                polygon_mode: supported_polygon_mode(self.device, self.polygon_mode),
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            fragment: Some(wgpu::FragmentState {
-                module: &shader_module,
-                entry_point: &self.fragment_entry,
-                targets: &render_targets,
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
+            depth_stencil: self.depth_stencil.clone(),
+            fragment,
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: self.sample_count,
                 mask: !0,
-                alpha_to_coverage_enabled: false,
+                alpha_to_coverage_enabled: self.alpha_to_coverage && self.sample_count > 1,
             },
             multiview: None,
             cache: None,
@@ -132,7 +319,26 @@ impl<'a> Builder<'a> {
         let render_pipeline = self
             .device
             .create_render_pipeline(&render_pipeline_descriptor);
-        self.reset();
-        render_pipeline
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(error) => Err(format!("{}: {}", label, error)),
+            None => Ok(render_pipeline),
+        }
+    }
+}
+
+// `polygon_mode`, or Fill when the device lacks the POLYGON_MODE_* feature it needs
+pub fn supported_polygon_mode(
+    device: &wgpu::Device,
+    polygon_mode: wgpu::PolygonMode,
+) -> wgpu::PolygonMode {
+    let required_feature = match polygon_mode {
+        wgpu::PolygonMode::Fill => return wgpu::PolygonMode::Fill,
+        wgpu::PolygonMode::Line => wgpu::Features::POLYGON_MODE_LINE,
+        wgpu::PolygonMode::Point => wgpu::Features::POLYGON_MODE_POINT,
+    };
+    if device.features().contains(required_feature) {
+        polygon_mode
+    } else {
+        wgpu::PolygonMode::Fill
     }
 }
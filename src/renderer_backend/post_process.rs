@@ -0,0 +1,245 @@
+use super::{
+    bind_group, bind_group_layout, pipeline, preprocessor::Preprocessor,
+    reflection::ShaderReflection, texture,
+};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VignetteUniform {
+    pub intensity: f32,
+    pub radius: f32,
+    pub softness: f32,
+    pub _padding: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SharpenUniform {
+    pub strength: f32,
+    pub _padding: [f32; 3],
+}
+
+// an effect is a fragment shader including common/post_process.wgsl,
+// with at most one uniform buffer, `params`, in @group(1)
+pub struct PostEffect {
+    pub name: String,
+    pub enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: Option<wgpu::Buffer>,
+    uniform_bind_group: Option<wgpu::BindGroup>,
+}
+
+pub struct PostProcessChain {
+    pub effects: Vec<PostEffect>,
+    format: wgpu::TextureFormat,
+    ping_pong: [texture::Texture; 2],
+    input_layout: wgpu::BindGroupLayout,
+    input_bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl PostProcessChain {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let input_layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_texture();
+            builder.build("Post Process Input Layout")
+        };
+        let ping_pong = Self::create_ping_pong(device, width, height, format);
+        let input_bind_groups = Self::create_input_bind_groups(device, &input_layout, &ping_pong);
+
+        Self {
+            effects: Vec::new(),
+            format,
+            ping_pong,
+            input_layout,
+            input_bind_groups,
+        }
+    }
+
+    fn create_ping_pong(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> [texture::Texture; 2] {
+        [
+            texture::Texture::create_render_target(
+                device,
+                width,
+                height,
+                format,
+                "Post Process Ping",
+            ),
+            texture::Texture::create_render_target(
+                device,
+                width,
+                height,
+                format,
+                "Post Process Pong",
+            ),
+        ]
+    }
+
+    fn create_input_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        ping_pong: &[texture::Texture; 2],
+    ) -> [wgpu::BindGroup; 2] {
+        ping_pong.each_ref().map(|texture| {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(layout);
+            builder.add_texture(&texture.view, &texture.sampler);
+            builder.build("Post Process Input Bind Group")
+        })
+    }
+
+    pub fn add_effect(&mut self, device: &wgpu::Device, name: &str, shader_filename: &str) {
+        let reflection = ShaderReflection::load(shader_filename, &Preprocessor::new());
+        let uniform_entries = reflection
+            .bind_group_layout_entries(1)
+            .unwrap_or_else(|message| panic!("{}", message));
+        let uniform_layout = (!uniform_entries.is_empty()).then(|| {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_reflected_entries(std::slice::from_ref(&reflection), 1);
+            builder.build(&format!("{} Uniform Layout", name))
+        });
+
+        let pipeline = {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_fullscreen_shader(shader_filename, "fragment_main");
+            builder.set_pixel_format(self.format);
+            builder.add_bind_group_layout(&self.input_layout);
+            if let Some(uniform_layout) = &uniform_layout {
+                builder.add_bind_group_layout(uniform_layout);
+            }
+            builder.build_pipeline(name)
+        };
+
+        let uniform_buffer = uniform_entries.first().map(|entry| {
+            let size = match entry.ty {
+                wgpu::BindingType::Buffer {
+                    min_binding_size: Some(size),
+                    ..
+                } => size.get(),
+                _ => panic!(
+                    "{}: group 1 must hold a single uniform buffer, `params`",
+                    shader_filename
+                ),
+            };
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("{} Uniform Buffer", name)),
+                size: size.next_multiple_of(16),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+        let uniform_bind_group = uniform_layout.as_ref().map(|uniform_layout| {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_reflected_layout(uniform_layout, std::slice::from_ref(&reflection), 1);
+            builder.bind(
+                "params",
+                uniform_buffer.as_ref().unwrap().as_entire_binding(),
+            );
+            builder.build(&format!("{} Uniform Bind Group", name))
+        });
+
+        self.effects.push(PostEffect {
+            name: name.to_string(),
+            enabled: true,
+            pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+        });
+    }
+
+    fn effect_mut(&mut self, name: &str) -> &mut PostEffect {
+        self.effects
+            .iter_mut()
+            .find(|effect| effect.name == name)
+            .unwrap_or_else(|| panic!("no post process effect named {}", name))
+    }
+
+    pub fn set_uniforms<T: bytemuck::Pod>(
+        &mut self,
+        queue: &wgpu::Queue,
+        name: &str,
+        uniforms: &T,
+    ) {
+        let effect = self.effect_mut(name);
+        let buffer = effect
+            .uniform_buffer
+            .as_ref()
+            .unwrap_or_else(|| panic!("post process effect {} has no uniforms", name));
+        queue.write_buffer(buffer, 0, bytemuck::bytes_of(uniforms));
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        self.effect_mut(name).enabled = enabled;
+    }
+
+    pub fn toggle(&mut self, index: usize) -> Option<&PostEffect> {
+        let effect = self.effects.get_mut(index)?;
+        effect.enabled = !effect.enabled;
+        Some(effect)
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.ping_pong = Self::create_ping_pong(device, width, height, self.format);
+        self.input_bind_groups =
+            Self::create_input_bind_groups(device, &self.input_layout, &self.ping_pong);
+    }
+
+    // runs the enabled effects in order, reading from and writing back to `target`,
+    // which must have the size given to new/resize
+    pub fn apply(&self, command_encoder: &mut wgpu::CommandEncoder, target: &texture::Texture) {
+        let enabled = self
+            .effects
+            .iter()
+            .filter(|effect| effect.enabled)
+            .collect::<Vec<_>>();
+        if enabled.is_empty() {
+            return;
+        }
+
+        command_encoder.copy_texture_to_texture(
+            target.texture.as_image_copy(),
+            self.ping_pong[0].texture.as_image_copy(),
+            target.texture.size(),
+        );
+
+        for (index, effect) in enabled.iter().enumerate() {
+            let input = index % 2;
+            let output = if index == enabled.len() - 1 {
+                &target.view
+            } else {
+                &self.ping_pong[1 - input].view
+            };
+
+            let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&effect.name),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&effect.pipeline);
+            render_pass.set_bind_group(0, &self.input_bind_groups[input], &[]);
+            if let Some(uniform_bind_group) = &effect.uniform_bind_group {
+                render_pass.set_bind_group(1, uniform_bind_group, &[]);
+            }
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
@@ -0,0 +1,585 @@
+use std::collections::{HashMap, HashSet};
+use std::env::current_dir;
+use std::fmt;
+use std::fs;
+
+// Supported directives:
+//   #include "relative/path.wgsl"   (relative to the including file, included once)
+//   #define NAME [value]
+//   #undef NAME
+//   #ifdef NAME / #ifndef NAME / #else / #endif
+// Defines with a value are substituted on every identifier matching their name, outside of
+// `//` comments.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    MissingInclude {
+        location: SourceLocation,
+        path: String,
+    },
+    MalformedDirective {
+        location: SourceLocation,
+        directive: String,
+    },
+    UnknownDirective {
+        location: SourceLocation,
+        directive: String,
+    },
+    UnexpectedDirective {
+        location: SourceLocation,
+        directive: String,
+    },
+    UnterminatedConditional {
+        location: SourceLocation,
+    },
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingInclude { location, path } => {
+                write!(f, "{}: cannot include \"{}\"", location, path)
+            }
+            Self::MalformedDirective {
+                location,
+                directive,
+            } => write!(f, "{}: malformed directive `{}`", location, directive),
+            Self::UnknownDirective {
+                location,
+                directive,
+            } => write!(f, "{}: unknown directive `{}`", location, directive),
+            Self::UnexpectedDirective {
+                location,
+                directive,
+            } => write!(f, "{}: `{}` without matching #ifdef", location, directive),
+            Self::UnterminatedConditional { location } => {
+                write!(f, "{}: conditional block is never closed", location)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+pub struct ProcessedShader {
+    pub filename: String,
+    pub source: String,
+    // source location of every output line
+    pub lines: Vec<SourceLocation>,
+}
+
+impl ProcessedShader {
+    // `line` is 1-based, as reported by naga
+    pub fn source_location(&self, line: usize) -> Option<&SourceLocation> {
+        self.lines.get(line.checked_sub(1)?)
+    }
+
+    fn format_span(&self, span: naga::Span, message: &str) -> String {
+        let location = span.location(&self.source);
+        match self.source_location(location.line_number as usize) {
+            Some(original) => format!("{}:{}: {}", original, location.line_position, message),
+            None => format!("{}: {}", self.filename, message),
+        }
+    }
+
+    pub fn parse(&self) -> Result<naga::Module, String> {
+        naga::front::wgsl::parse_str(&self.source).map_err(|error| {
+            let mut message = match error.labels().next() {
+                Some((span, _)) => self.format_span(span, error.message()),
+                None => format!("{}: {}", self.filename, error.message()),
+            };
+            for (span, label) in error.labels() {
+                message.push_str(&format!("\n    {}", self.format_span(span, label)));
+            }
+            message
+        })
+    }
+
+    pub fn validate(&self) -> Result<(naga::Module, naga::valid::ModuleInfo), String> {
+        let module = self.parse()?;
+        let mut validator = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        );
+        let info = validator.validate(&module).map_err(|error| {
+            let inner = error.as_inner().to_string();
+            let mut message = match error.spans().next() {
+                Some((span, _)) => self.format_span(*span, &inner),
+                None => format!("{}: {}", self.filename, inner),
+            };
+            for (span, label) in error.spans() {
+                message.push_str(&format!("\n    {}", self.format_span(*span, label)));
+            }
+            message
+        })?;
+        Ok((module, info))
+    }
+
+    // the errors of wgpu are caught too, instead of reaching the uncaptured error handler
+    pub fn try_create_shader_module(
+        &self,
+        device: &wgpu::Device,
+    ) -> Result<wgpu::ShaderModule, String> {
+        if let Err(message) = self.validate() {
+            return Err(format!("shader error in {}\n{}", self.filename, message));
+        }
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&self.filename),
+            source: wgpu::ShaderSource::Wgsl(self.source.as_str().into()),
+        });
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(error) => Err(format!("shader error in {}\n{}", self.filename, error)),
+            None => Ok(module),
+        }
+    }
+}
+
+struct Conditional {
+    active: bool,
+    seen_else: bool,
+    location: SourceLocation,
+}
+
+struct State {
+    defines: HashMap<String, String>,
+    included: HashSet<String>,
+    source: String,
+    lines: Vec<SourceLocation>,
+}
+
+#[derive(Clone, Default)]
+pub struct Preprocessor {
+    defines: HashMap<String, String>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn define(&mut self, name: &str, value: &str) {
+        self.defines.insert(name.to_string(), value.to_string());
+    }
+
+    // `load` receives the include path resolved against the including file
+    pub fn process<F>(
+        &self,
+        filename: &str,
+        source: &str,
+        mut load: F,
+    ) -> Result<ProcessedShader, PreprocessError>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let mut state = State {
+            defines: self.defines.clone(),
+            included: HashSet::from([filename.to_string()]),
+            source: String::new(),
+            lines: Vec::new(),
+        };
+        Self::process_file(filename, source, &mut load, &mut state)?;
+        Ok(ProcessedShader {
+            filename: filename.to_string(),
+            source: state.source,
+            lines: state.lines,
+        })
+    }
+
+    pub fn load(&self, filename: &str) -> ProcessedShader {
+        self.try_load(filename)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // for shaders edited while running, which should not bring the app down
+    pub fn try_load(&self, filename: &str) -> Result<ProcessedShader, String> {
+        let source = read_shader(filename)
+            .ok_or_else(|| format!("Can't read the source code of {}", filename))?;
+        self.process(filename, &source, read_shader)
+            .map_err(|error| error.to_string())
+    }
+
+    fn process_file<F>(
+        filename: &str,
+        source: &str,
+        load: &mut F,
+        state: &mut State,
+    ) -> Result<(), PreprocessError>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let mut conditionals: Vec<Conditional> = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let location = SourceLocation {
+                file: filename.to_string(),
+                line: index + 1,
+            };
+            let active = conditionals.iter().all(|conditional| conditional.active);
+            let trimmed = line.trim_start();
+
+            if !trimmed.starts_with('#') {
+                if active {
+                    state.source.push_str(&substitute(line, &state.defines));
+                    state.source.push('\n');
+                    state.lines.push(location);
+                }
+                continue;
+            }
+
+            let mut parts = trimmed[1..].splitn(2, char::is_whitespace);
+            let directive = parts.next().unwrap_or("");
+            let argument = parts.next().unwrap_or("").trim();
+            let malformed = || PreprocessError::MalformedDirective {
+                location: location.clone(),
+                directive: trimmed.to_string(),
+            };
+
+            match directive {
+                "ifdef" | "ifndef" => {
+                    let name = identifier(argument).ok_or_else(malformed)?;
+                    let defined = state.defines.contains_key(name);
+                    conditionals.push(Conditional {
+                        active: defined == (directive == "ifdef"),
+                        seen_else: false,
+                        location: location.clone(),
+                    });
+                }
+                "else" => match conditionals.last_mut() {
+                    Some(conditional) if !conditional.seen_else => {
+                        conditional.active = !conditional.active;
+                        conditional.seen_else = true;
+                    }
+                    _ => {
+                        return Err(PreprocessError::UnexpectedDirective {
+                            location,
+                            directive: "#else".to_string(),
+                        })
+                    }
+                },
+                "endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(PreprocessError::UnexpectedDirective {
+                            location,
+                            directive: "#endif".to_string(),
+                        });
+                    }
+                }
+                _ if !active => {}
+                "define" => {
+                    let mut define = argument.splitn(2, char::is_whitespace);
+                    let name = define.next().and_then(identifier).ok_or_else(malformed)?;
+                    let value = define.next().unwrap_or("").trim();
+                    let value = substitute(value, &state.defines);
+                    state.defines.insert(name.to_string(), value);
+                }
+                "undef" => {
+                    let name = identifier(argument).ok_or_else(malformed)?;
+                    state.defines.remove(name);
+                }
+                "include" => {
+                    let path = argument
+                        .strip_prefix('"')
+                        .and_then(|path| path.strip_suffix('"'))
+                        .ok_or_else(malformed)?;
+                    let resolved = resolve_include(filename, path);
+                    if !state.included.insert(resolved.clone()) {
+                        continue;
+                    }
+                    let included_source =
+                        load(&resolved).ok_or_else(|| PreprocessError::MissingInclude {
+                            location: location.clone(),
+                            path: path.to_string(),
+                        })?;
+                    Self::process_file(&resolved, &included_source, load, state)?;
+                }
+                _ => {
+                    return Err(PreprocessError::UnknownDirective {
+                        location,
+                        directive: format!("#{}", directive),
+                    })
+                }
+            }
+        }
+
+        match conditionals.pop() {
+            Some(conditional) => Err(PreprocessError::UnterminatedConditional {
+                location: conditional.location,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+fn read_shader(filename: &str) -> Option<String> {
+    let mut filepath = current_dir().unwrap();
+    filepath.push("src/");
+    filepath.push(filename);
+    fs::read_to_string(filepath).ok()
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn identifier(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let valid = text.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && text.chars().all(is_identifier_char);
+    valid.then_some(text)
+}
+
+// whole identifiers only, the numbers and the `//` comment of the line being kept as is
+fn substitute(line: &str, defines: &HashMap<String, String>) -> String {
+    if defines.values().all(String::is_empty) {
+        return line.to_string();
+    }
+
+    let (code, comment) = line.split_at(line.find("//").unwrap_or(line.len()));
+    let mut output = String::with_capacity(line.len());
+    let mut rest = code;
+    while let Some(start) = rest.find(|c: char| is_identifier_char(c)) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !is_identifier_char(c))
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        match defines.get(word) {
+            Some(value) if !value.is_empty() && !word.starts_with(|c: char| c.is_ascii_digit()) => {
+                output.push_str(value)
+            }
+            _ => output.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output.push_str(comment);
+    output
+}
+
+fn resolve_include(current_file: &str, path: &str) -> String {
+    let mut components: Vec<&str> = current_file.split('/').collect();
+    components.pop();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `files` by path, as the loader of Preprocessor::process
+    fn process(
+        preprocessor: &Preprocessor,
+        source: &str,
+        files: &[(&str, &str)],
+    ) -> Result<ProcessedShader, PreprocessError> {
+        preprocessor.process("shaders/main.wgsl", source, |path| {
+            files
+                .iter()
+                .find(|(name, _)| *name == path)
+                .map(|(_, source)| source.to_string())
+        })
+    }
+
+    fn lines(shader: &ProcessedShader) -> Vec<&str> {
+        shader.source.lines().collect()
+    }
+
+    fn location(file: &str, line: usize) -> SourceLocation {
+        SourceLocation {
+            file: file.to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn nested_includes_are_resolved_against_the_including_file() {
+        let files = [
+            ("shaders/common/a.wgsl", "a1\n#include \"../b.wgsl\"\na3"),
+            ("shaders/b.wgsl", "b1"),
+        ];
+        let shader = process(
+            &Preprocessor::new(),
+            "main1\n#include \"common/a.wgsl\"\nmain3",
+            &files,
+        )
+        .unwrap();
+        assert_eq!(lines(&shader), ["main1", "a1", "b1", "a3", "main3"]);
+    }
+
+    #[test]
+    fn files_are_included_once() {
+        let files = [
+            ("shaders/a.wgsl", "a1\n#include \"b.wgsl\""),
+            (
+                "shaders/b.wgsl",
+                "b1\n#include \"a.wgsl\"\n#include \"main.wgsl\"",
+            ),
+        ];
+        let source = "#include \"a.wgsl\"\n#include \"b.wgsl\"\n#include \"a.wgsl\"\nmain4";
+        let shader = process(&Preprocessor::new(), source, &files).unwrap();
+        assert_eq!(lines(&shader), ["a1", "b1", "main4"]);
+    }
+
+    #[test]
+    fn missing_include_is_an_error() {
+        let result = process(&Preprocessor::new(), "main1\n#include \"nope.wgsl\"", &[]);
+        match result {
+            Err(PreprocessError::MissingInclude { location: at, path }) => {
+                assert_eq!(at, location("shaders/main.wgsl", 2));
+                assert_eq!(path, "nope.wgsl");
+            }
+            _ => panic!("expected a missing include"),
+        }
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let source = "\
+#ifdef A
+a
+#ifndef B
+a_not_b
+#else
+a_b
+#endif
+#else
+not_a
+#ifdef B
+not_a_b
+#endif
+#endif
+end";
+        let run = |defines: &[&str]| {
+            let mut preprocessor = Preprocessor::new();
+            for define in defines {
+                preprocessor.define(define, "");
+            }
+            lines(&process(&preprocessor, source, &[]).unwrap())
+                .into_iter()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(&[]), ["not_a", "end"]);
+        assert_eq!(run(&["A"]), ["a", "a_not_b", "end"]);
+        assert_eq!(run(&["A", "B"]), ["a", "a_b", "end"]);
+        assert_eq!(run(&["B"]), ["not_a", "not_a_b", "end"]);
+    }
+
+    #[test]
+    fn defines_inside_inactive_blocks_are_ignored() {
+        let source =
+            "#ifdef A\n#define B\n#endif\n#ifdef B\nb\n#endif\n#define C\n#ifdef C\nc\n#endif";
+        let shader = process(&Preprocessor::new(), source, &[]).unwrap();
+        assert_eq!(lines(&shader), ["c"]);
+    }
+
+    #[test]
+    fn unmatched_conditionals_are_errors() {
+        let preprocessor = Preprocessor::new();
+        match process(&preprocessor, "a\n#endif", &[]) {
+            Err(PreprocessError::UnexpectedDirective {
+                location: at,
+                directive,
+            }) => {
+                assert_eq!(at, location("shaders/main.wgsl", 2));
+                assert_eq!(directive, "#endif");
+            }
+            _ => panic!("expected an unexpected #endif"),
+        }
+        assert!(matches!(
+            process(&preprocessor, "#ifdef A\n#else\n#else\n#endif", &[]),
+            Err(PreprocessError::UnexpectedDirective { .. })
+        ));
+        match process(&preprocessor, "a\n#ifndef A\nb", &[]) {
+            Err(PreprocessError::UnterminatedConditional { location: at }) => {
+                assert_eq!(at, location("shaders/main.wgsl", 2));
+            }
+            _ => panic!("expected an unterminated conditional"),
+        }
+    }
+
+    #[test]
+    fn lines_map_back_to_their_source() {
+        let files = [("shaders/a.wgsl", "// a\na2")];
+        let source = "main1\n#ifdef A\nmain3\n#endif\n#include \"a.wgsl\"\nmain6";
+        let shader = process(&Preprocessor::new(), source, &files).unwrap();
+        assert_eq!(lines(&shader), ["main1", "// a", "a2", "main6"]);
+        assert_eq!(
+            shader.lines,
+            [
+                location("shaders/main.wgsl", 1),
+                location("shaders/a.wgsl", 1),
+                location("shaders/a.wgsl", 2),
+                location("shaders/main.wgsl", 6),
+            ]
+        );
+        assert_eq!(
+            shader.source_location(3),
+            Some(&location("shaders/a.wgsl", 2))
+        );
+        assert_eq!(shader.source_location(0), None);
+        assert_eq!(shader.source_location(5), None);
+    }
+
+    #[test]
+    fn defines_replace_whole_identifiers_outside_of_comments() {
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.define("SIZE", "4u");
+        let source = "\
+let a = SIZE;
+let b = SIZE_MAX + MY_SIZE + SIZE2;
+let c = array<f32, SIZE>(); // SIZE elements
+#define HALF SIZE / 2u
+let d = HALF;";
+        let shader = process(&preprocessor, source, &[]).unwrap();
+        assert_eq!(
+            lines(&shader),
+            [
+                "let a = 4u;",
+                "let b = SIZE_MAX + MY_SIZE + SIZE2;",
+                "let c = array<f32, 4u>(); // SIZE elements",
+                "let d = 4u / 2u;",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_and_malformed_directives_are_errors() {
+        let preprocessor = Preprocessor::new();
+        assert!(matches!(
+            process(&preprocessor, "#pragma once", &[]),
+            Err(PreprocessError::UnknownDirective { .. })
+        ));
+        assert!(matches!(
+            process(&preprocessor, "#include a.wgsl", &[]),
+            Err(PreprocessError::MalformedDirective { .. })
+        ));
+        assert!(matches!(
+            process(&preprocessor, "#ifdef 2A\n#endif", &[]),
+            Err(PreprocessError::MalformedDirective { .. })
+        ));
+    }
+}
@@ -0,0 +1,776 @@
+use std::num::NonZeroU64;
+
+use super::preprocessor::{Preprocessor, ProcessedShader};
+
+#[derive(Debug, Clone)]
+pub struct VertexInput {
+    pub name: String,
+    pub location: u32,
+    pub kind: naga::ScalarKind,
+    pub components: u32,
+    // in bytes, 8 for f64
+    pub width: u8,
+}
+
+impl VertexInput {
+    fn type_name(&self) -> String {
+        let scalar = match self.kind {
+            naga::ScalarKind::Sint => "i32",
+            naga::ScalarKind::Uint => "u32",
+            naga::ScalarKind::Bool => "bool",
+            _ if self.width == 8 => "f64",
+            _ => "f32",
+        };
+        match self.components {
+            1 => scalar.to_string(),
+            n => format!("vec{}<{}>", n, scalar),
+        }
+    }
+}
+
+pub struct ShaderReflection {
+    pub filename: String,
+    module: naga::Module,
+    info: naga::valid::ModuleInfo,
+}
+
+impl ShaderReflection {
+    pub fn new(shader: &ProcessedShader) -> Self {
+        Self::try_new(shader).unwrap_or_else(|message| panic!("{}", message))
+    }
+
+    pub fn try_new(shader: &ProcessedShader) -> Result<Self, String> {
+        let (module, info) = shader
+            .validate()
+            .map_err(|message| format!("shader error in {}\n{}", shader.filename, message))?;
+        Ok(Self {
+            filename: shader.filename.clone(),
+            module,
+            info,
+        })
+    }
+
+    pub fn load(filename: &str, preprocessor: &Preprocessor) -> Self {
+        Self::new(&preprocessor.load(filename))
+    }
+
+    pub fn has_entry_point(&self, name: &str, stage: naga::ShaderStage) -> bool {
+        self.module
+            .entry_points
+            .iter()
+            .any(|entry| entry.name == name && entry.stage == stage)
+    }
+
+    fn visibility(&self, handle: naga::Handle<naga::GlobalVariable>) -> wgpu::ShaderStages {
+        let mut visibility = wgpu::ShaderStages::NONE;
+        for (index, entry_point) in self.module.entry_points.iter().enumerate() {
+            if self.info.get_entry_point(index)[handle].is_empty() {
+                continue;
+            }
+            visibility |= match entry_point.stage {
+                naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+            };
+        }
+        visibility
+    }
+
+    // entries sorted by binding index, visible to every stage that uses them
+    pub fn bind_group_layout_entries(
+        &self,
+        group: u32,
+    ) -> Result<Vec<wgpu::BindGroupLayoutEntry>, String> {
+        let mut entries = self
+            .module
+            .global_variables
+            .iter()
+            .filter_map(|(handle, global)| {
+                let binding = global.binding.as_ref()?;
+                if binding.group != group {
+                    return None;
+                }
+                Some(
+                    self.binding_type(global)
+                        .map(|(ty, count)| wgpu::BindGroupLayoutEntry {
+                            binding: binding.binding,
+                            visibility: self.visibility(handle),
+                            ty,
+                            count,
+                        }),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.binding);
+        Ok(entries)
+    }
+
+    // entries of `group` over every permutation of a shader, visible to the stages of all of
+    // them. A binding must have the same type in each
+    pub fn merged_bind_group_layout_entries(
+        reflections: &[ShaderReflection],
+        group: u32,
+    ) -> Result<Vec<wgpu::BindGroupLayoutEntry>, String> {
+        let mut merged: Vec<wgpu::BindGroupLayoutEntry> = Vec::new();
+        for reflection in reflections {
+            for entry in reflection.bind_group_layout_entries(group)? {
+                match merged
+                    .iter_mut()
+                    .find(|other| other.binding == entry.binding)
+                {
+                    Some(other) if other.ty != entry.ty || other.count != entry.count => {
+                        return Err(format!(
+                            "{}: @group({}) @binding({}) has a different type depending on the defines",
+                            reflection.filename, group, entry.binding
+                        ));
+                    }
+                    Some(other) => other.visibility |= entry.visibility,
+                    None => merged.push(entry),
+                }
+            }
+        }
+        merged.sort_by_key(|entry| entry.binding);
+        Ok(merged)
+    }
+
+    // binding index of the global variable `name` in `group`
+    pub fn binding_index(&self, group: u32, name: &str) -> Option<u32> {
+        self.module.global_variables.iter().find_map(|(_, global)| {
+            let binding = global.binding.as_ref()?;
+            (binding.group == group && global.name.as_deref() == Some(name))
+                .then_some(binding.binding)
+        })
+    }
+
+    fn binding_type(
+        &self,
+        global: &naga::GlobalVariable,
+    ) -> Result<(wgpu::BindingType, Option<std::num::NonZeroU32>), String> {
+        let mut ty = &self.module.types[global.ty].inner;
+        let mut count = None;
+        if let naga::TypeInner::BindingArray { base, size } = ty {
+            if let naga::ArraySize::Constant(size) = size {
+                count = Some(*size);
+            }
+            ty = &self.module.types[*base].inner;
+        }
+
+        let min_binding_size = NonZeroU64::new(ty.size(self.module.to_ctx()) as u64);
+        let binding_type = match global.space {
+            naga::AddressSpace::Uniform => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size,
+            },
+            naga::AddressSpace::Storage { access } => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage {
+                    read_only: !access.contains(naga::StorageAccess::STORE),
+                },
+                has_dynamic_offset: false,
+                min_binding_size,
+            },
+            _ => match ty {
+                naga::TypeInner::Sampler { comparison: true } => {
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)
+                }
+                naga::TypeInner::Sampler { comparison: false } => {
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+                }
+                naga::TypeInner::Image {
+                    dim,
+                    arrayed,
+                    class,
+                } => image_binding_type(*dim, *arrayed, *class),
+                _ => {
+                    return Err(format!(
+                        "{}: unsupported binding type for `{}`",
+                        self.filename,
+                        global.name.as_deref().unwrap_or("?")
+                    ))
+                }
+            },
+        };
+        Ok((binding_type, count))
+    }
+
+    pub fn vertex_inputs(&self, entry_point: &str) -> Result<Vec<VertexInput>, String> {
+        let entry_point = self
+            .module
+            .entry_points
+            .iter()
+            .find(|entry| entry.name == entry_point)
+            .ok_or_else(|| format!("{}: no entry point `{}`", self.filename, entry_point))?;
+
+        let mut inputs = Vec::new();
+        for argument in &entry_point.function.arguments {
+            let name = argument.name.clone().unwrap_or_default();
+            match &self.module.types[argument.ty].inner {
+                naga::TypeInner::Struct { members, .. } => {
+                    for member in members {
+                        let member_name =
+                            format!("{}.{}", name, member.name.as_deref().unwrap_or("?"));
+                        self.push_vertex_input(
+                            &mut inputs,
+                            member_name,
+                            &member.binding,
+                            member.ty,
+                        );
+                    }
+                }
+                _ => self.push_vertex_input(&mut inputs, name, &argument.binding, argument.ty),
+            }
+        }
+        inputs.sort_by_key(|input| input.location);
+        Ok(inputs)
+    }
+
+    fn push_vertex_input(
+        &self,
+        inputs: &mut Vec<VertexInput>,
+        name: String,
+        binding: &Option<naga::Binding>,
+        ty: naga::Handle<naga::Type>,
+    ) {
+        let Some(naga::Binding::Location { location, .. }) = binding else {
+            return;
+        };
+        let (scalar, components) = match self.module.types[ty].inner {
+            naga::TypeInner::Scalar(scalar) => (scalar, 1),
+            naga::TypeInner::Vector { size, scalar } => (scalar, size as u32),
+            _ => return,
+        };
+        inputs.push(VertexInput {
+            name,
+            location: *location,
+            kind: scalar.kind,
+            components,
+            width: scalar.width,
+        });
+    }
+
+    // every shader input must be fed by exactly one attribute of a compatible type
+    pub fn validate_vertex_buffers(
+        &self,
+        entry_point: &str,
+        layouts: &[wgpu::VertexBufferLayout],
+    ) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let attributes = layouts
+            .iter()
+            .enumerate()
+            .flat_map(|(buffer, layout)| {
+                layout
+                    .attributes
+                    .iter()
+                    .map(move |attribute| (buffer, attribute))
+            })
+            .collect::<Vec<_>>();
+
+        for (index, (buffer, attribute)) in attributes.iter().enumerate() {
+            if let Some((other, _)) = attributes[..index]
+                .iter()
+                .find(|(_, other)| other.shader_location == attribute.shader_location)
+            {
+                errors.push(format!(
+                    "location {} is provided by both buffer {} and buffer {}",
+                    attribute.shader_location, other, buffer
+                ));
+            }
+        }
+
+        let inputs = self
+            .vertex_inputs(entry_point)
+            .map_err(|message| vec![message])?;
+        for input in inputs {
+            let Some((buffer, attribute)) = attributes
+                .iter()
+                .find(|(_, attribute)| attribute.shader_location == input.location)
+            else {
+                errors.push(format!(
+                    "location {} (`{}`: {}) is not provided by any vertex buffer",
+                    input.location,
+                    input.name,
+                    input.type_name()
+                ));
+                continue;
+            };
+            // wgpu pads or drops the components that differ, which is never intended here
+            if vertex_format_type(attribute.format) != (input.kind, input.components, input.width) {
+                errors.push(format!(
+                    "location {} (`{}`): shader expects {} but buffer {} provides {:?}",
+                    input.location,
+                    input.name,
+                    input.type_name(),
+                    buffer,
+                    attribute.format
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn image_binding_type(
+    dim: naga::ImageDimension,
+    arrayed: bool,
+    class: naga::ImageClass,
+) -> wgpu::BindingType {
+    let view_dimension = match (dim, arrayed) {
+        (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    };
+    match class {
+        naga::ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+            sample_type: match kind {
+                naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                _ => wgpu::TextureSampleType::Float { filterable: !multi },
+            },
+            view_dimension,
+            multisampled: multi,
+        },
+        naga::ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Depth,
+            view_dimension,
+            multisampled: multi,
+        },
+        naga::ImageClass::Storage { format, access } => wgpu::BindingType::StorageTexture {
+            access: match (
+                access.contains(naga::StorageAccess::LOAD),
+                access.contains(naga::StorageAccess::STORE),
+            ) {
+                (true, true) => wgpu::StorageTextureAccess::ReadWrite,
+                (true, false) => wgpu::StorageTextureAccess::ReadOnly,
+                _ => wgpu::StorageTextureAccess::WriteOnly,
+            },
+            format: storage_format(format),
+            view_dimension,
+        },
+    }
+}
+
+fn storage_format(format: naga::StorageFormat) -> wgpu::TextureFormat {
+    use naga::StorageFormat as Sf;
+    use wgpu::TextureFormat as Tf;
+    match format {
+        Sf::R8Unorm => Tf::R8Unorm,
+        Sf::R8Snorm => Tf::R8Snorm,
+        Sf::R8Uint => Tf::R8Uint,
+        Sf::R8Sint => Tf::R8Sint,
+        Sf::R16Uint => Tf::R16Uint,
+        Sf::R16Sint => Tf::R16Sint,
+        Sf::R16Float => Tf::R16Float,
+        Sf::Rg8Unorm => Tf::Rg8Unorm,
+        Sf::Rg8Snorm => Tf::Rg8Snorm,
+        Sf::Rg8Uint => Tf::Rg8Uint,
+        Sf::Rg8Sint => Tf::Rg8Sint,
+        Sf::R32Uint => Tf::R32Uint,
+        Sf::R32Sint => Tf::R32Sint,
+        Sf::R32Float => Tf::R32Float,
+        Sf::Rg16Uint => Tf::Rg16Uint,
+        Sf::Rg16Sint => Tf::Rg16Sint,
+        Sf::Rg16Float => Tf::Rg16Float,
+        Sf::Rgba8Unorm => Tf::Rgba8Unorm,
+        Sf::Rgba8Snorm => Tf::Rgba8Snorm,
+        Sf::Rgba8Uint => Tf::Rgba8Uint,
+        Sf::Rgba8Sint => Tf::Rgba8Sint,
+        Sf::Bgra8Unorm => Tf::Bgra8Unorm,
+        Sf::Rgb10a2Uint => Tf::Rgb10a2Uint,
+        Sf::Rgb10a2Unorm => Tf::Rgb10a2Unorm,
+        Sf::Rg11b10Float => Tf::Rg11b10Float,
+        Sf::Rg32Uint => Tf::Rg32Uint,
+        Sf::Rg32Sint => Tf::Rg32Sint,
+        Sf::Rg32Float => Tf::Rg32Float,
+        Sf::Rgba16Uint => Tf::Rgba16Uint,
+        Sf::Rgba16Sint => Tf::Rgba16Sint,
+        Sf::Rgba16Float => Tf::Rgba16Float,
+        Sf::Rgba32Uint => Tf::Rgba32Uint,
+        Sf::Rgba32Sint => Tf::Rgba32Sint,
+        Sf::Rgba32Float => Tf::Rgba32Float,
+        Sf::R16Unorm => Tf::R16Unorm,
+        Sf::R16Snorm => Tf::R16Snorm,
+        Sf::Rg16Unorm => Tf::Rg16Unorm,
+        Sf::Rg16Snorm => Tf::Rg16Snorm,
+        Sf::Rgba16Unorm => Tf::Rgba16Unorm,
+        Sf::Rgba16Snorm => Tf::Rgba16Snorm,
+    }
+}
+
+// scalar kind, component count and scalar width the shader sees when reading an attribute
+// of this format
+fn vertex_format_type(format: wgpu::VertexFormat) -> (naga::ScalarKind, u32, u8) {
+    use wgpu::VertexFormat as Vf;
+    let kind = match format {
+        Vf::Uint8x2
+        | Vf::Uint8x4
+        | Vf::Uint16x2
+        | Vf::Uint16x4
+        | Vf::Uint32
+        | Vf::Uint32x2
+        | Vf::Uint32x3
+        | Vf::Uint32x4 => naga::ScalarKind::Uint,
+        Vf::Sint8x2
+        | Vf::Sint8x4
+        | Vf::Sint16x2
+        | Vf::Sint16x4
+        | Vf::Sint32
+        | Vf::Sint32x2
+        | Vf::Sint32x3
+        | Vf::Sint32x4 => naga::ScalarKind::Sint,
+        _ => naga::ScalarKind::Float,
+    };
+    let components = match format {
+        Vf::Uint32 | Vf::Sint32 | Vf::Float32 | Vf::Float64 => 1,
+        Vf::Float32x3 | Vf::Uint32x3 | Vf::Sint32x3 | Vf::Float64x3 => 3,
+        Vf::Uint8x4
+        | Vf::Sint8x4
+        | Vf::Unorm8x4
+        | Vf::Snorm8x4
+        | Vf::Uint16x4
+        | Vf::Sint16x4
+        | Vf::Unorm16x4
+        | Vf::Snorm16x4
+        | Vf::Float16x4
+        | Vf::Float32x4
+        | Vf::Uint32x4
+        | Vf::Sint32x4
+        | Vf::Float64x4
+        | Vf::Unorm10_10_10_2 => 4,
+        _ => 2,
+    };
+    let width = match format {
+        Vf::Float64 | Vf::Float64x2 | Vf::Float64x3 | Vf::Float64x4 => 8,
+        _ => 4,
+    };
+    (kind, components, width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERTEX_SHADER: &str = "
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) light: u32,
+}
+
+@vertex
+fn vertex_main(vertex: VertexInput, @location(3) scale: f32) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(vertex.position * scale, f32(vertex.light) + vertex.tex_coords.x);
+}
+";
+
+    fn reflect(source: &str) -> ShaderReflection {
+        reflect_with(&Preprocessor::new(), source)
+    }
+
+    fn reflect_with(preprocessor: &Preprocessor, source: &str) -> ShaderReflection {
+        let shader = preprocessor.process("test.wgsl", source, |_| None).unwrap();
+        ShaderReflection::try_new(&shader).unwrap()
+    }
+
+    fn attribute(shader_location: u32, format: wgpu::VertexFormat) -> wgpu::VertexAttribute {
+        wgpu::VertexAttribute {
+            format,
+            offset: 0,
+            shader_location,
+        }
+    }
+
+    fn layout(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: 64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes,
+        }
+    }
+
+    // the errors of VERTEX_SHADER fed by a single buffer, the formats being given by location
+    fn validate(formats: &[(u32, wgpu::VertexFormat)]) -> Vec<String> {
+        let attributes = formats
+            .iter()
+            .map(|&(location, format)| attribute(location, format))
+            .collect::<Vec<_>>();
+        reflect(VERTEX_SHADER)
+            .validate_vertex_buffers("vertex_main", &[layout(&attributes)])
+            .err()
+            .unwrap_or_default()
+    }
+
+    const MATCHING: [(u32, wgpu::VertexFormat); 4] = [
+        (0, wgpu::VertexFormat::Float32x3),
+        (1, wgpu::VertexFormat::Float32x2),
+        (2, wgpu::VertexFormat::Uint32),
+        (3, wgpu::VertexFormat::Float32),
+    ];
+
+    fn with(location: u32, format: wgpu::VertexFormat) -> Vec<(u32, wgpu::VertexFormat)> {
+        let mut formats = MATCHING.to_vec();
+        formats[location as usize].1 = format;
+        formats
+    }
+
+    #[test]
+    fn vertex_inputs_are_read_from_structs_and_arguments() {
+        let inputs = reflect(VERTEX_SHADER).vertex_inputs("vertex_main").unwrap();
+        let inputs = inputs
+            .iter()
+            .map(|input| (input.name.as_str(), input.location, input.type_name()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            inputs,
+            [
+                ("vertex.position", 0, "vec3<f32>".to_string()),
+                ("vertex.tex_coords", 1, "vec2<f32>".to_string()),
+                ("vertex.light", 2, "u32".to_string()),
+                ("scale", 3, "f32".to_string()),
+            ]
+        );
+        assert!(reflect(VERTEX_SHADER).vertex_inputs("missing").is_err());
+    }
+
+    #[test]
+    fn matching_buffers_are_valid() {
+        assert_eq!(validate(&MATCHING), Vec::<String>::new());
+        // normalized formats are read as floats
+        assert_eq!(
+            validate(&with(1, wgpu::VertexFormat::Unorm16x2)),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn scalar_kind_mismatch_is_reported() {
+        let errors = validate(&with(2, wgpu::VertexFormat::Sint32));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("location 2"), "{}", errors[0]);
+        assert!(errors[0].contains("expects u32"), "{}", errors[0]);
+
+        assert_eq!(validate(&with(3, wgpu::VertexFormat::Uint32)).len(), 1);
+    }
+
+    #[test]
+    fn component_count_mismatch_is_reported() {
+        let errors = validate(&with(0, wgpu::VertexFormat::Float32x4));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("expects vec3<f32>"), "{}", errors[0]);
+        assert!(errors[0].contains("Float32x4"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn scalar_width_mismatch_is_reported() {
+        let errors = validate(&with(3, wgpu::VertexFormat::Float64));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("location 3"), "{}", errors[0]);
+        assert!(errors[0].contains("Float64"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn missing_location_is_reported() {
+        let errors = validate(&MATCHING[..3]);
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].contains("location 3 (`scale`: f32) is not provided"),
+            "{}",
+            errors[0]
+        );
+    }
+
+    #[test]
+    fn duplicate_location_is_reported() {
+        let attributes = MATCHING
+            .iter()
+            .map(|&(location, format)| attribute(location, format))
+            .collect::<Vec<_>>();
+        let instance_attributes = [attribute(1, wgpu::VertexFormat::Float32x2)];
+        let errors = reflect(VERTEX_SHADER)
+            .validate_vertex_buffers(
+                "vertex_main",
+                &[layout(&attributes), layout(&instance_attributes)],
+            )
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            ["location 1 is provided by both buffer 0 and buffer 1"]
+        );
+    }
+
+    #[test]
+    fn layout_entries_follow_the_bindings_and_their_use() {
+        let reflection = reflect(
+            "
+struct Params {
+    scale: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(3) var<storage, read> values: array<f32>;
+@group(0) @binding(1) var<storage, read_write> results: array<f32>;
+@group(1) @binding(0) var color: texture_2d<f32>;
+@group(1) @binding(1) var color_sampler: sampler;
+@group(1) @binding(2) var depth: texture_depth_2d;
+@group(1) @binding(3) var shadow_sampler: sampler_comparison;
+@group(1) @binding(4) var output: texture_storage_2d<rgba8unorm, write>;
+@group(1) @binding(5) var layers: texture_2d_array<u32>;
+
+@vertex
+fn vertex_main() -> @builtin(position) vec4<f32> {
+    return params.scale;
+}
+
+@fragment
+fn fragment_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    let shadow = textureSampleCompare(depth, shadow_sampler, position.xy, 0.5);
+    let layer = f32(textureLoad(layers, coords, 0, 0).x);
+    return textureSample(color, color_sampler, position.xy) * params.scale * shadow * layer;
+}
+
+@compute @workgroup_size(1)
+fn compute_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    results[id.x] = values[id.x];
+    textureStore(output, vec2<u32>(id.xy), vec4<f32>(1.0));
+}
+",
+        );
+
+        let group0 = reflection.bind_group_layout_entries(0).unwrap();
+        let bindings = group0
+            .iter()
+            .map(|entry| (entry.binding, entry.visibility))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bindings,
+            [
+                (0, wgpu::ShaderStages::VERTEX_FRAGMENT),
+                (1, wgpu::ShaderStages::COMPUTE),
+                (3, wgpu::ShaderStages::COMPUTE),
+            ]
+        );
+        assert_eq!(
+            group0[0].ty,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(16),
+            }
+        );
+        assert!(matches!(
+            group0[1].ty,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                ..
+            }
+        ));
+        assert!(matches!(
+            group0[2].ty,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                ..
+            }
+        ));
+
+        let group1 = reflection.bind_group_layout_entries(1).unwrap();
+        let types = group1.iter().map(|entry| entry.ty).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Uint,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+            ]
+        );
+        assert!(group1[..4]
+            .iter()
+            .all(|entry| entry.visibility == wgpu::ShaderStages::FRAGMENT));
+        assert_eq!(group1[4].visibility, wgpu::ShaderStages::COMPUTE);
+        assert!(reflection.bind_group_layout_entries(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn permutations_are_merged_into_one_layout() {
+        let source = "
+@group(0) @binding(0) var<uniform> scale: vec4<f32>;
+#ifdef TINTED
+@group(0) @binding(2) var<uniform> tint: vec4<f32>;
+#endif
+
+@vertex
+fn vertex_main() -> @builtin(position) vec4<f32> {
+    return scale;
+}
+
+@fragment
+fn fragment_main() -> @location(0) vec4<f32> {
+#ifdef TINTED
+    return tint * scale;
+#else
+    return vec4<f32>(1.0);
+#endif
+}
+";
+        let mut tinted = Preprocessor::new();
+        tinted.define("TINTED", "");
+        let reflections = [reflect(source), reflect_with(&tinted, source)];
+        assert_eq!(reflections[0].binding_index(0, "tint"), None);
+        assert_eq!(reflections[1].binding_index(0, "tint"), Some(2));
+
+        let entries = ShaderReflection::merged_bind_group_layout_entries(&reflections, 0).unwrap();
+        let bindings = entries
+            .iter()
+            .map(|entry| (entry.binding, entry.visibility))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bindings,
+            [
+                (0, wgpu::ShaderStages::VERTEX_FRAGMENT),
+                (2, wgpu::ShaderStages::FRAGMENT),
+            ]
+        );
+
+        let retyped = reflect(
+            "
+@group(0) @binding(0) var<uniform> scale: f32;
+
+@vertex
+fn vertex_main() -> @builtin(position) vec4<f32> {
+    return vec4<f32>(scale);
+}
+",
+        );
+        assert!(
+            ShaderReflection::merged_bind_group_layout_entries(&[reflect(source), retyped], 0)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use cgmath::{InnerSpace, Vector3};
+
+use super::{
+    bind_group, bind_group_layout, cubemap, environment::Lighting, hdr::HDR_FORMAT, pipeline,
+    texture,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkyMode {
+    Procedural = 0,
+    Cubemap = 1,
+    Gradient = 2,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+    sun_direction: [f32; 3],
+    sun_intensity: f32,
+    horizon_color: [f32; 3],
+    mode: u32,
+    zenith_color: [f32; 3],
+    _padding: f32,
+}
+
+// drawn in the scene pass after the opaque geometry, on the pixels still at max depth
+pub struct Sky {
+    pub mode: SkyMode,
+    // towards the sun, used by the procedural sky
+    pub sun_direction: Vector3<f32>,
+    pub sun_intensity: f32,
+    // used by the gradient sky
+    pub horizon_color: Vector3<f32>,
+    pub zenith_color: Vector3<f32>,
+    cubemap: Option<texture::Texture>,
+    params_buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Sky {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) -> Self {
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sky Params Buffer"),
+            size: std::mem::size_of::<SkyUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_buffer(wgpu::ShaderStages::FRAGMENT);
+            builder.add_cube_texture();
+            builder.build("Sky Bind Group Layout")
+        };
+
+        let pipeline = Self::create_pipeline(device, camera_layout, &layout, sample_count);
+
+        // bound while no cubemap is loaded
+        let placeholder = cubemap::create_cubemap(
+            device,
+            1,
+            HDR_FORMAT,
+            wgpu::TextureUsages::TEXTURE_BINDING,
+            "Sky Placeholder",
+        );
+        let bind_group = Self::create_bind_group(device, &layout, &params_buffer, &placeholder);
+
+        Self {
+            mode: SkyMode::Procedural,
+            sun_direction: Vector3::new(0.3, 0.6, 0.2).normalize(),
+            sun_intensity: 20.0,
+            horizon_color: Vector3::new(0.6, 0.75, 0.9),
+            zenith_color: Vector3::new(0.15, 0.35, 0.8),
+            cubemap: None,
+            params_buffer,
+            layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_shader_module("shaders/sky.wgsl", "vertex_main", "fragment_main");
+        builder.set_pixel_format(HDR_FORMAT);
+        builder.set_cull_mode(None);
+        builder.set_depth_compare(wgpu::CompareFunction::LessEqual);
+        builder.set_depth_write(false);
+        builder.set_sample_count(sample_count);
+        builder.add_bind_group_layout(camera_layout);
+        builder.add_bind_group_layout(layout);
+        builder.build_pipeline("Sky Pipeline")
+    }
+
+    // the one of the scene pass, which the sky is drawn in
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) {
+        self.pipeline = Self::create_pipeline(device, camera_layout, &self.layout, sample_count);
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params_buffer: &wgpu::Buffer,
+        cubemap: &texture::Texture,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_layout(layout);
+        builder.add_buffer(params_buffer);
+        builder.add_texture(&cubemap.view, &cubemap.sampler);
+        builder.build("Sky Bind Group")
+    }
+
+    // see the cubemap module to load one, switches to Cubemap mode
+    pub fn set_cubemap(&mut self, device: &wgpu::Device, cubemap: texture::Texture) {
+        self.bind_group =
+            Self::create_bind_group(device, &self.layout, &self.params_buffer, &cubemap);
+        self.cubemap = Some(cubemap);
+        self.mode = SkyMode::Cubemap;
+    }
+
+    pub fn has_cubemap(&self) -> bool {
+        self.cubemap.is_some()
+    }
+
+    pub fn next_mode(&mut self) {
+        self.mode = match self.mode {
+            SkyMode::Procedural => SkyMode::Gradient,
+            SkyMode::Gradient if self.has_cubemap() => SkyMode::Cubemap,
+            _ => SkyMode::Procedural,
+        };
+    }
+
+    pub fn set_lighting(&mut self, lighting: &Lighting) {
+        self.sun_direction = lighting.sun_direction;
+        self.horizon_color = lighting.horizon_color;
+        self.zenith_color = lighting.zenith_color;
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let sky_uniform = SkyUniform {
+            sun_direction: self.sun_direction.normalize().into(),
+            sun_intensity: self.sun_intensity,
+            horizon_color: self.horizon_color.into(),
+            mode: self.mode as u32,
+            zenith_color: self.zenith_color.into(),
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[sky_uniform]));
+    }
+
+    // the pass needs the scene depth attachment, `camera_bind_group` matches the
+    // layout given to new
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,274 @@
+use super::{bind_group, bind_group_layout, model::Vertex, pipeline, texture};
+
+const FONTS_DIR: &str = "fonts";
+// the atlas is a grid of equally sized cells, holding the printable ASCII characters in order
+// (res/fonts/monospace.png: DejaVu Sans Mono at 20px in 12x24 cells, see res/fonts/LICENSE)
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_ROWS: u32 = 6;
+const FIRST_GLYPH: char = ' ';
+const LAST_GLYPH: char = '~';
+// drawn for the characters missing from the atlas
+const REPLACEMENT_GLYPH: char = '?';
+const INITIAL_GLYPH_CAPACITY: usize = 1024;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TextVertex {
+    // pixels from the top left corner of the screen
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Vertex for TextVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ScreenUniform {
+    size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+// monospace bitmap font text, queued with add_text during the frame and drawn in a single
+// call over the final image
+pub struct TextRenderer {
+    // pixels of a glyph at scale 1
+    glyph_size: (f32, f32),
+    vertices: Vec<TextVertex>,
+    vertex_buffer: wgpu::Buffer,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    font_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        output_format: wgpu::TextureFormat,
+        font_filename: &str,
+    ) -> Self {
+        let mut filepath = std::env::current_dir().unwrap();
+        filepath.push(crate::RESSOURCES_DIR);
+        filepath.push(FONTS_DIR);
+        filepath.push(font_filename);
+        let bytes = std::fs::read(&filepath)
+            .unwrap_or_else(|e| panic!("cannot load font {}: {}", filepath.display(), e));
+        let image = image::load_from_memory(&bytes).unwrap();
+        assert!(
+            image.width().is_multiple_of(ATLAS_COLUMNS)
+                && image.height().is_multiple_of(ATLAS_ROWS),
+            "{} is not a {}x{} glyph grid",
+            filepath.display(),
+            ATLAS_COLUMNS,
+            ATLAS_ROWS
+        );
+        let glyph_size = (
+            (image.width() / ATLAS_COLUMNS) as f32,
+            (image.height() / ATLAS_ROWS) as f32,
+        );
+
+        // pixel exact at integer scales
+        let font = texture::Texture::from_image_with_options(
+            &image,
+            device,
+            queue,
+            None,
+            Some("Font Texture"),
+            &texture::TextureOptions {
+                generate_mipmaps: false,
+                sampler: texture::SamplerConfig {
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    address_mode_w: wgpu::AddressMode::ClampToEdge,
+                    ..Default::default()
+                },
+            },
+        );
+
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Screen Buffer"),
+            size: std::mem::size_of::<ScreenUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_buffer(wgpu::ShaderStages::VERTEX);
+            builder.build("Text Screen Bind Group Layout")
+        };
+        let screen_bind_group = {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&screen_layout);
+            builder.add_buffer(&screen_buffer);
+            builder.build("Text Screen Bind Group")
+        };
+        let font_layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_texture();
+            builder.build("Font Bind Group Layout")
+        };
+        let font_bind_group = {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&font_layout);
+            builder.add_texture(&font.view, &font.sampler);
+            builder.build("Font Bind Group")
+        };
+
+        let pipeline = {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_shader_module("shaders/text.wgsl", "vertex_main", "fragment_main");
+            builder.set_pixel_format(output_format);
+            builder.set_blend_preset(pipeline::BlendPreset::Alpha);
+            builder.set_cull_mode(None);
+            builder.disable_depth();
+            builder.add_vertex_buffer_layout(TextVertex::desc());
+            builder.add_bind_group_layout(&screen_layout);
+            builder.add_bind_group_layout(&font_layout);
+            builder.build_pipeline("Text Pipeline")
+        };
+
+        Self {
+            glyph_size,
+            vertices: Vec::new(),
+            vertex_buffer: Self::create_vertex_buffer(device, INITIAL_GLYPH_CAPACITY * 6),
+            screen_buffer,
+            screen_bind_group,
+            font_bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, vertex_count: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Vertex Buffer"),
+            size: (vertex_count * std::mem::size_of::<TextVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // `position` is the top left corner of the first line in pixels, each '\n' starting a
+    // new line below it. Colors are linear, straight alpha
+    pub fn add_text(&mut self, text: &str, position: (f32, f32), scale: f32, color: [f32; 4]) {
+        let (width, height) = (self.glyph_size.0 * scale, self.glyph_size.1 * scale);
+        for (row, line) in text.lines().enumerate() {
+            let y = position.1 + row as f32 * height;
+            for (column, glyph) in line.chars().enumerate() {
+                if glyph == ' ' {
+                    continue;
+                }
+                let x = position.0 + column as f32 * width;
+                let [u0, v0, u1, v1] = Self::glyph_tex_coords(glyph);
+                let corners = [
+                    ([x, y], [u0, v0]),
+                    ([x + width, y], [u1, v0]),
+                    ([x + width, y + height], [u1, v1]),
+                    ([x, y + height], [u0, v1]),
+                ];
+                self.vertices.extend([0, 1, 2, 0, 2, 3].map(|i| TextVertex {
+                    position: corners[i].0,
+                    tex_coords: corners[i].1,
+                    color,
+                }));
+            }
+        }
+    }
+
+    fn glyph_tex_coords(glyph: char) -> [f32; 4] {
+        let glyph = if (FIRST_GLYPH..=LAST_GLYPH).contains(&glyph) {
+            glyph
+        } else {
+            REPLACEMENT_GLYPH
+        };
+        let index = glyph as u32 - FIRST_GLYPH as u32;
+        let (column, row) = (index % ATLAS_COLUMNS, index / ATLAS_COLUMNS);
+        let (width, height) = (1.0 / ATLAS_COLUMNS as f32, 1.0 / ATLAS_ROWS as f32);
+        [
+            column as f32 * width,
+            row as f32 * height,
+            (column + 1) as f32 * width,
+            (row + 1) as f32 * height,
+        ]
+    }
+
+    // draws everything added since the last call over `output`, of `size` pixels
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        command_encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        size: (u32, u32),
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let vertex_size = std::mem::size_of::<TextVertex>() as wgpu::BufferAddress;
+        let vertex_count = self.vertices.len();
+        if vertex_count as wgpu::BufferAddress * vertex_size > self.vertex_buffer.size() {
+            self.vertex_buffer =
+                Self::create_vertex_buffer(device, vertex_count.next_power_of_two());
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[ScreenUniform {
+                size: [size.0 as f32, size.1 as f32],
+                _padding: [0.0; 2],
+            }]),
+        );
+        self.vertices.clear();
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Text Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.screen_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.font_bind_group, &[]);
+        render_pass.set_vertex_buffer(
+            0,
+            self.vertex_buffer
+                .slice(..vertex_count as wgpu::BufferAddress * vertex_size),
+        );
+        render_pass.draw(0..vertex_count as u32, 0..1);
+    }
+}
@@ -1,21 +1,97 @@
-use super::bind_group;
+use wgpu::util::DeviceExt;
+
+use super::{bind_group, mipmap};
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    pub params_buffer: Option<wgpu::Buffer>,
     pub bind_group: Option<wgpu::BindGroup>,
 }
 
 const TEXTURES_DIR: &str = "textures/";
 
+#[derive(Debug, Clone, Copy)]
+pub struct SamplerConfig {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    // only applied when all three filters are Linear
+    pub anisotropy: u16,
+    // added to the mip level picked by the hardware, see TextureParams in shader.wgsl
+    pub lod_bias: f32,
+}
+
+impl Default for SamplerConfig {
+    // crisp texels up close, blended mip levels in the distance
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy: 1,
+            lod_bias: 0.0,
+        }
+    }
+}
+
+impl SamplerConfig {
+    pub fn create_sampler(&self, device: &wgpu::Device, label: Option<&str>) -> wgpu::Sampler {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|filter| *filter == wgpu::FilterMode::Linear);
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_w,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: if all_linear {
+                self.anisotropy.clamp(1, 16)
+            } else {
+                1
+            },
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextureOptions {
+    pub generate_mipmaps: bool,
+    pub sampler: SamplerConfig,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextureParamsUniform {
+    lod_bias: f32,
+    _padding: [f32; 3],
+}
+
 impl Texture {
-    pub fn load_image(filename: &str) -> image::DynamicImage {
+    pub fn image_path(filename: &str) -> std::path::PathBuf {
         let mut filepath = std::env::current_dir().unwrap();
         filepath.push(crate::RESSOURCES_DIR);
         filepath.push(TEXTURES_DIR);
         filepath.push(filename);
-        let filepath = filepath.into_os_string().into_string().unwrap();
+        filepath
+    }
+
+    pub fn load_image(filename: &str) -> image::DynamicImage {
+        let filepath = Self::image_path(filename)
+            .into_os_string()
+            .into_string()
+            .unwrap();
 
         let bytes = std::fs::read(&filepath).expect(&format!("cannot load texture: {}", filepath));
         image::load_from_memory(&bytes).unwrap()
@@ -27,6 +103,24 @@ impl Texture {
         queue: &wgpu::Queue,
         layout: Option<&wgpu::BindGroupLayout>,
         label: Option<&str>,
+    ) -> Self {
+        Self::from_image_with_options(
+            image,
+            device,
+            queue,
+            layout,
+            label,
+            &TextureOptions::default(),
+        )
+    }
+
+    pub fn from_image_with_options(
+        image: &image::DynamicImage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: Option<&wgpu::BindGroupLayout>,
+        label: Option<&str>,
+        options: &TextureOptions,
     ) -> Self {
         let diffuse_rgba = image.to_rgba8();
         let dimensions = diffuse_rgba.dimensions();
@@ -37,14 +131,28 @@ impl Texture {
             depth_or_array_layers: 1,
         };
 
+        let (mip_level_count, usage) = if options.generate_mipmaps {
+            (
+                mipmap::mip_level_count(dimensions.0, dimensions.1),
+                wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST
+                    | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+        } else {
+            (
+                1,
+                wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            )
+        };
+
         let texture_descriptor = wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            usage,
             view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
         };
         let texture = device.create_texture(&texture_descriptor);
@@ -65,41 +173,169 @@ impl Texture {
             size,
         );
 
+        if options.generate_mipmaps {
+            mipmap::generate_mipmaps(device, queue, &texture);
+        }
+
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = options.sampler.create_sampler(device, label);
 
-        let sampler_descriptor = wgpu::SamplerDescriptor {
-            label,
-            address_mode_u: wgpu::AddressMode::Repeat,
-            address_mode_v: wgpu::AddressMode::Repeat,
-            address_mode_w: wgpu::AddressMode::Repeat,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Texture Params Buffer"),
+            contents: bytemuck::cast_slice(&[TextureParamsUniform {
+                lod_bias: options.sampler.lod_bias,
+                _padding: [0.0; 3],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let mut texture = Self {
+            texture,
+            view,
+            sampler,
+            params_buffer: Some(params_buffer),
+            bind_group: None,
         };
-        let sampler = device.create_sampler(&sampler_descriptor);
+        if let Some(layout) = layout {
+            texture.bind_group = Some(texture.create_bind_group(
+                device,
+                layout,
+                label.unwrap_or("default texute bind group"),
+            ));
+        }
+        texture
+    }
 
-        let bind_group = if layout.is_some() {
-            let mut builder = bind_group::Builder::new(device);
-            builder.set_layout(layout.unwrap());
-            builder.add_texture(&view, &sampler);
-            Some(builder.build(label.unwrap_or("default texute bind group")))
-        } else {
-			None
-		};
+    // matches default_layout: texture, sampler and sampling parameters
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        label: &str,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_layout(layout);
+        builder.add_texture(&self.view, &self.sampler);
+        builder.add_buffer(
+            self.params_buffer
+                .as_ref()
+                .expect("only image textures can be bound with sampling parameters"),
+        );
+        builder.build(label)
+    }
+
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    // counts every format supports, in increasing order. The counts other than 1 and 4 need
+    // the TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES feature enabled on the device
+    pub fn supported_sample_counts(
+        adapter: &wgpu::Adapter,
+        device_features: wgpu::Features,
+        formats: &[wgpu::TextureFormat],
+    ) -> Vec<u32> {
+        let adapter_specific =
+            device_features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        [1, 2, 4, 8]
+            .into_iter()
+            .filter(|count| adapter_specific || matches!(count, 1 | 4))
+            .filter(|count| {
+                formats.iter().all(|format| {
+                    adapter
+                        .get_texture_format_features(*format)
+                        .flags
+                        .sample_count_supported(*count)
+                })
+            })
+            .collect()
+    }
+
+    pub fn create_multisampled_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let desc = wgpu::TextureDescriptor {
+            label: Some("Multisampled Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&desc);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
 
         Self {
             texture,
             view,
             sampler,
-            bind_group,
+            params_buffer: None,
+            bind_group: None,
         }
     }
 
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    // offscreen color target that later passes can sample
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&desc);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            params_buffer: None,
+            bind_group: None,
+        }
+    }
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
     ) -> Self {
         let size = wgpu::Extent3d {
             width: config.width.max(1),
@@ -110,7 +346,7 @@ impl Texture {
             label: Some("Depth Texture"),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
@@ -137,6 +373,7 @@ impl Texture {
             texture,
             view,
             sampler,
+            params_buffer: None,
             bind_group: None,
         }
     }
@@ -144,6 +381,7 @@ impl Texture {
     pub fn default_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         let mut builder = super::bind_group_layout::Builder::new(device);
         builder.add_texture();
+        builder.add_buffer(wgpu::ShaderStages::FRAGMENT);
         builder.build("Texture Bind Group Layout")
     }
 }
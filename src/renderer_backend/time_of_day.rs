@@ -0,0 +1,131 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use cgmath::{InnerSpace, Vector3};
+
+use super::environment::Lighting;
+
+// tilt of the sun path towards +z, so it is never exactly at the zenith
+const SUN_PATH_TILT: f32 = 0.35;
+const SUN_ILLUMINANCE: f32 = 3.0;
+
+const NIGHT_ZENITH: Vector3<f32> = Vector3::new(0.004, 0.006, 0.015);
+const DAY_ZENITH: Vector3<f32> = Vector3::new(0.15, 0.35, 0.8);
+const NIGHT_HORIZON: Vector3<f32> = Vector3::new(0.015, 0.02, 0.04);
+const DAY_HORIZON: Vector3<f32> = Vector3::new(0.6, 0.75, 0.9);
+const TWILIGHT_HORIZON: Vector3<f32> = Vector3::new(0.5, 0.2, 0.05);
+const NIGHT_AMBIENT: Vector3<f32> = Vector3::new(0.02, 0.025, 0.05);
+const DAY_AMBIENT: Vector3<f32> = Vector3::new(0.25, 0.3, 0.4);
+const SUNSET_COLOR: Vector3<f32> = Vector3::new(1.0, 0.45, 0.15);
+const NOON_COLOR: Vector3<f32> = Vector3::new(1.0, 0.95, 0.88);
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn mix(a: Vector3<f32>, b: Vector3<f32>, t: f32) -> Vector3<f32> {
+    a + (b - a) * t
+}
+
+// world time, only advanced by `update` and the scrub controls so that the same
+// sequence of calls always gives the same lighting
+#[derive(Debug, Clone)]
+pub struct TimeOfDay {
+    pub elapsed: Duration,
+    pub day_length: Duration,
+    pub paused: bool,
+}
+
+impl TimeOfDay {
+    // starts in the morning of day 0
+    pub fn new(day_length: Duration) -> Self {
+        Self {
+            elapsed: day_length.mul_f32(0.3),
+            day_length,
+            paused: false,
+        }
+    }
+
+    pub fn update(&mut self, delta_time: Duration) {
+        if !self.paused {
+            self.elapsed += delta_time;
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // moves by a fraction of a day, negative to go back in time
+    pub fn scrub(&mut self, days: f32) {
+        let offset = self.day_length.mul_f32(days.abs());
+        self.elapsed = if days < 0.0 {
+            self.elapsed.saturating_sub(offset)
+        } else {
+            self.elapsed + offset
+        };
+    }
+
+    pub fn day(&self) -> u64 {
+        (self.elapsed.as_nanos() / self.day_length.as_nanos().max(1)) as u64
+    }
+
+    // in [0, 1), 0 being midnight, 0.25 sunrise and 0.5 noon
+    pub fn time(&self) -> f32 {
+        let day_length = self.day_length.as_nanos().max(1);
+        (self.elapsed.as_nanos() % day_length) as f32 / day_length as f32
+    }
+
+    // as hours and minutes
+    pub fn clock(&self) -> (u32, u32) {
+        let minutes = (self.time() * 24.0 * 60.0) as u32;
+        (minutes / 60, minutes % 60)
+    }
+
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let angle = (self.time() - 0.25) * std::f32::consts::TAU;
+        let (sin, cos) = angle.sin_cos();
+        Vector3::new(cos, sin * SUN_PATH_TILT.cos(), sin * SUN_PATH_TILT.sin()).normalize()
+    }
+
+    pub fn lighting(&self) -> Lighting {
+        let sun_direction = self.sun_direction();
+        let elevation = sun_direction.y;
+        let day = smoothstep(-0.1, 0.25, elevation);
+        let twilight = 1.0 - smoothstep(0.0, 0.3, elevation.abs());
+
+        let sun_color = mix(SUNSET_COLOR, NOON_COLOR, smoothstep(0.0, 0.4, elevation))
+            * smoothstep(-0.05, 0.05, elevation)
+            * SUN_ILLUMINANCE;
+        let horizon_color = mix(NIGHT_HORIZON, DAY_HORIZON, day) + TWILIGHT_HORIZON * twilight;
+
+        Lighting {
+            sun_direction,
+            sun_color,
+            ambient_color: mix(NIGHT_AMBIENT, DAY_AMBIENT, day),
+            horizon_color,
+            zenith_color: mix(NIGHT_ZENITH, DAY_ZENITH, day),
+            fog_color: horizon_color,
+        }
+    }
+
+    // the elapsed time in nanoseconds, exact so a reload gives the same lighting
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.elapsed.as_nanos().to_string())
+    }
+
+    pub fn load(path: &Path, day_length: Duration) -> io::Result<Self> {
+        let nanos = fs::read_to_string(path)?
+            .trim()
+            .parse::<u64>()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(Self {
+            elapsed: Duration::from_nanos(nanos),
+            day_length,
+            paused: false,
+        })
+    }
+}
@@ -0,0 +1,256 @@
+use cgmath::{EuclideanSpace, MetricSpace, Point3};
+
+use super::{bind_group, bind_group_layout, instance, pipeline, texture};
+
+pub const ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransparencyMode {
+    // alpha blended back to front, exact for non intersecting faces
+    Sorted,
+    // weighted blended order-independent transparency, no sorting but approximate
+    WeightedBlended,
+}
+
+impl TransparencyMode {
+    pub fn next(self) -> Self {
+        match self {
+            TransparencyMode::Sorted => TransparencyMode::WeightedBlended,
+            TransparencyMode::WeightedBlended => TransparencyMode::Sorted,
+        }
+    }
+}
+
+// only instances are translucent, the chunk meshes holding opaque blocks only
+pub fn sort_back_to_front(instances: &mut [&instance::Instance], camera_position: Point3<f32>) {
+    // from the center of the block, its position being a corner
+    let distance = |instance: &instance::Instance| {
+        camera_position.distance2(Point3::from_vec(instance.bounding_sphere().0))
+    };
+    instances.sort_unstable_by(|a, b| distance(b).total_cmp(&distance(a)));
+}
+
+// sets up a pipeline writing the OIT targets, its shader must be built with the OIT define
+pub fn configure_accumulate_pipeline(builder: &mut pipeline::Builder) {
+    builder.add_define("OIT", "");
+    builder.add_color_target(
+        ACCUM_FORMAT,
+        Some(wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        }),
+        wgpu::ColorWrites::ALL,
+    );
+    // product of (1 - alpha) over every fragment
+    builder.add_color_target(
+        REVEALAGE_FORMAT,
+        Some(wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        }),
+        wgpu::ColorWrites::ALL,
+    );
+    builder.set_depth_write(false);
+}
+
+struct OitTarget {
+    multisampled: Option<texture::Texture>,
+    resolved: texture::Texture,
+}
+
+impl OitTarget {
+    fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        Self {
+            multisampled: (sample_count > 1).then(|| {
+                texture::Texture::create_multisampled_texture(device, config, format, sample_count)
+            }),
+            resolved: texture::Texture::create_render_target(
+                device,
+                config.width,
+                config.height,
+                format,
+                label,
+            ),
+        }
+    }
+
+    fn attachment(&self, clear: wgpu::Color) -> wgpu::RenderPassColorAttachment<'_> {
+        let (view, resolve_target) = match &self.multisampled {
+            Some(multisampled) => (&multisampled.view, Some(&self.resolved.view)),
+            None => (&self.resolved.view, None),
+        };
+        wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(clear),
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+}
+
+// accumulates translucent fragments in its own targets, sharing the scene depth buffer,
+// then blends their average over the opaque scene
+pub struct WeightedBlendedOit {
+    sample_count: u32,
+    accum: OitTarget,
+    revealage: OitTarget,
+    composite_layout: wgpu::BindGroupLayout,
+    composite_bind_group: wgpu::BindGroup,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+impl WeightedBlendedOit {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        output_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let composite_layout = {
+            let mut builder = bind_group_layout::Builder::new(device);
+            for _ in 0..2 {
+                builder.add_texture_view(
+                    wgpu::ShaderStages::FRAGMENT,
+                    wgpu::TextureSampleType::Float { filterable: false },
+                    wgpu::TextureViewDimension::D2,
+                );
+            }
+            builder.build("OIT Composite Bind Group Layout")
+        };
+        let composite_pipeline = {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_fullscreen_shader("shaders/oit_composite.wgsl", "fragment_main");
+            builder.set_pixel_format(output_format);
+            builder.set_blend_preset(pipeline::BlendPreset::Alpha);
+            builder.add_bind_group_layout(&composite_layout);
+            builder.build_pipeline("OIT Composite Pipeline")
+        };
+
+        let accum = OitTarget::new(device, config, ACCUM_FORMAT, sample_count, "OIT Accum");
+        let revealage = OitTarget::new(
+            device,
+            config,
+            REVEALAGE_FORMAT,
+            sample_count,
+            "OIT Revealage",
+        );
+        let composite_bind_group =
+            Self::create_composite_bind_group(device, &composite_layout, &accum, &revealage);
+
+        Self {
+            sample_count,
+            accum,
+            revealage,
+            composite_layout,
+            composite_bind_group,
+            composite_pipeline,
+        }
+    }
+
+    fn create_composite_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        accum: &OitTarget,
+        revealage: &OitTarget,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_layout(layout);
+        builder.add_texture_view(&accum.resolved.view);
+        builder.add_texture_view(&revealage.resolved.view);
+        builder.build("OIT Composite Bind Group")
+    }
+
+    // used once the targets are created again by `resize`
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count;
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.accum = OitTarget::new(device, config, ACCUM_FORMAT, self.sample_count, "OIT Accum");
+        self.revealage = OitTarget::new(
+            device,
+            config,
+            REVEALAGE_FORMAT,
+            self.sample_count,
+            "OIT Revealage",
+        );
+        self.composite_bind_group = Self::create_composite_bind_group(
+            device,
+            &self.composite_layout,
+            &self.accum,
+            &self.revealage,
+        );
+    }
+
+    // draw the translucent geometry, in any order, with pipelines from
+    // configure_accumulate_pipeline
+    pub fn begin_accumulate_pass<'a>(
+        &self,
+        command_encoder: &'a mut wgpu::CommandEncoder,
+        depth_view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) -> wgpu::RenderPass<'a> {
+        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("OIT Accumulate Pass"),
+            color_attachments: &[
+                Some(self.accum.attachment(wgpu::Color::TRANSPARENT)),
+                Some(self.revealage.attachment(wgpu::Color::WHITE)),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes,
+            occlusion_query_set: None,
+        })
+    }
+
+    pub fn composite(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+    ) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("OIT Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &self.composite_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+pub const CHUNK_SIZE: i32 = 16;
+pub const CHUNK_HEIGHT: i32 = 64;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_HEIGHT) as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Block {
+    #[default]
+    Air,
+    Stone,
+    Dirt,
+    Grass,
+    Glowstone,
+}
+
+impl Block {
+    // hides the faces of its neighbors and darkens their ambient occlusion
+    pub fn is_opaque(self) -> bool {
+        self != Block::Air
+    }
+
+    // light lost when entering the block, None when it stops light entirely
+    pub fn light_attenuation(self) -> Option<u8> {
+        match self {
+            Block::Air => Some(1),
+            _ => None,
+        }
+    }
+
+    // block light level, from 0 to light::MAX_LIGHT
+    pub fn light_emission(self) -> u8 {
+        match self {
+            Block::Glowstone => 14,
+            _ => 0,
+        }
+    }
+}
+
+// chunks are full height columns of CHUNK_SIZE x CHUNK_SIZE blocks, indexed by x and z
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    pub fn of_block(x: i32, z: i32) -> Self {
+        Self {
+            x: x.div_euclid(CHUNK_SIZE),
+            z: z.div_euclid(CHUNK_SIZE),
+        }
+    }
+
+    // world coordinates of the block at local (0, 0, 0)
+    pub fn origin(self) -> (i32, i32) {
+        (self.x * CHUNK_SIZE, self.z * CHUNK_SIZE)
+    }
+}
+
+#[derive(Clone)]
+pub struct Chunk {
+    blocks: Vec<Block>,
+    // sky light in the high nibble, block light in the low one, see light.rs
+    light: Vec<u8>,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self {
+            blocks: vec![Block::Air; CHUNK_VOLUME],
+            light: vec![0; CHUNK_VOLUME],
+        }
+    }
+}
+
+impl Chunk {
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        (x + z * CHUNK_SIZE + y * CHUNK_SIZE * CHUNK_SIZE) as usize
+    }
+
+    // local coordinates, x and z in [0, CHUNK_SIZE), y in [0, CHUNK_HEIGHT)
+    pub fn get(&self, x: i32, y: i32, z: i32) -> Block {
+        self.blocks[Self::index(x, y, z)]
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, z: i32, block: Block) {
+        self.blocks[Self::index(x, y, z)] = block;
+    }
+
+    pub fn get_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.light[Self::index(x, y, z)]
+    }
+
+    pub fn set_light(&mut self, x: i32, y: i32, z: i32, light: u8) {
+        self.light[Self::index(x, y, z)] = light;
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct World {
+    pub chunks: HashMap<ChunkPos, Chunk>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Air outside of the loaded chunks and the height range
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Block {
+        if !(0..CHUNK_HEIGHT).contains(&y) {
+            return Block::Air;
+        }
+        let chunk_pos = ChunkPos::of_block(x, z);
+        match self.chunks.get(&chunk_pos) {
+            Some(chunk) => chunk.get(x.rem_euclid(CHUNK_SIZE), y, z.rem_euclid(CHUNK_SIZE)),
+            None => Block::Air,
+        }
+    }
+
+    pub fn is_loaded(&self, x: i32, y: i32, z: i32) -> bool {
+        (0..CHUNK_HEIGHT).contains(&y) && self.chunks.contains_key(&ChunkPos::of_block(x, z))
+    }
+
+    // packed light, full sky light above the world and outside of the loaded chunks
+    pub fn get_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        if y < 0 {
+            return 0;
+        }
+        if y >= CHUNK_HEIGHT {
+            return 0xf0;
+        }
+        match self.chunks.get(&ChunkPos::of_block(x, z)) {
+            Some(chunk) => chunk.get_light(x.rem_euclid(CHUNK_SIZE), y, z.rem_euclid(CHUNK_SIZE)),
+            None => 0xf0,
+        }
+    }
+
+    // ignored outside of the loaded chunks
+    pub fn set_light(&mut self, x: i32, y: i32, z: i32, light: u8) {
+        if !(0..CHUNK_HEIGHT).contains(&y) {
+            return;
+        }
+        if let Some(chunk) = self.chunks.get_mut(&ChunkPos::of_block(x, z)) {
+            chunk.set_light(x.rem_euclid(CHUNK_SIZE), y, z.rem_euclid(CHUNK_SIZE), light);
+        }
+    }
+
+    // creates the chunk if needed, ignored outside of the height range
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: Block) {
+        if !(0..CHUNK_HEIGHT).contains(&y) {
+            return;
+        }
+        self.chunks
+            .entry(ChunkPos::of_block(x, z))
+            .or_default()
+            .set(x.rem_euclid(CHUNK_SIZE), y, z.rem_euclid(CHUNK_SIZE), block);
+    }
+
+    // rolling hills with a few caves lit by glowstone, over the chunks from `min` to
+    // `max` included, the same seed always giving the same terrain.
+    // The light is not computed, see light::light_world
+    pub fn generate(seed: u32, min: ChunkPos, max: ChunkPos) -> Self {
+        let mut world = Self::new();
+        for chunk_x in min.x..=max.x {
+            for chunk_z in min.z..=max.z {
+                let chunk_pos = ChunkPos::new(chunk_x, chunk_z);
+                world
+                    .chunks
+                    .insert(chunk_pos, generate_chunk(seed, chunk_pos));
+            }
+        }
+        world
+    }
+}
+
+fn generate_chunk(seed: u32, chunk_pos: ChunkPos) -> Chunk {
+    let mut chunk = Chunk::default();
+    let (origin_x, origin_z) = chunk_pos.origin();
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            let world_x = (origin_x + x) as f32;
+            let world_z = (origin_z + z) as f32;
+            let noise = value_noise(seed, world_x / 32.0, world_z / 32.0) * 0.75
+                + value_noise(seed.wrapping_add(1), world_x / 8.0, world_z / 8.0) * 0.25;
+            let height = (8.0 + noise * 16.0) as i32;
+            for y in 0..=height.min(CHUNK_HEIGHT - 1) {
+                let block = match height - y {
+                    0 => Block::Grass,
+                    1..=3 => Block::Dirt,
+                    _ => Block::Stone,
+                };
+                chunk.set(x, y, z, block);
+            }
+            // caves stay a few blocks under the surface, so that most of them are closed
+            for y in 2..height - 3 {
+                let world_y = y as f32;
+                if value_noise_3d(seed, world_x / 12.0, world_y / 8.0, world_z / 12.0) > 0.62 {
+                    chunk.set(x, y, z, Block::Air);
+                }
+            }
+        }
+    }
+
+    // on the floor of the caves
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for y in 1..CHUNK_HEIGHT - 1 {
+                let is_floor = chunk.get(x, y, z) == Block::Air
+                    && chunk.get(x, y - 1, z) == Block::Stone
+                    && chunk.get(x, y + 1, z) == Block::Air;
+                if is_floor && hash_3d(seed, origin_x + x, y, origin_z + z) < 0.02 {
+                    chunk.set(x, y - 1, z, Block::Glowstone);
+                }
+            }
+        }
+    }
+    chunk
+}
+
+fn hash(seed: u32, x: i32, z: i32) -> f32 {
+    let mut h = seed
+        .wrapping_mul(0x27d4_eb2d)
+        .wrapping_add((x as u32).wrapping_mul(0x85eb_ca6b))
+        .wrapping_add((z as u32).wrapping_mul(0xc2b2_ae35));
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a_2d39);
+    h ^= h >> 15;
+    h as f32 / u32::MAX as f32
+}
+
+fn hash_3d(seed: u32, x: i32, y: i32, z: i32) -> f32 {
+    hash(seed ^ (y as u32).wrapping_mul(0x1656_67b1), x, z)
+}
+
+// smoothly interpolated random values on the integer grid, in [0, 1]
+fn value_noise(seed: u32, x: f32, z: f32) -> f32 {
+    let (x0, z0) = (x.floor() as i32, z.floor() as i32);
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, tz) = (smooth(x - x0 as f32), smooth(z - z0 as f32));
+    let top = hash(seed, x0, z0) + (hash(seed, x0 + 1, z0) - hash(seed, x0, z0)) * tx;
+    let bottom =
+        hash(seed, x0, z0 + 1) + (hash(seed, x0 + 1, z0 + 1) - hash(seed, x0, z0 + 1)) * tx;
+    top + (bottom - top) * tz
+}
+
+fn value_noise_3d(seed: u32, x: f32, y: f32, z: f32) -> f32 {
+    let y0 = y.floor() as i32;
+    let ty = y - y0 as f32;
+    let ty = ty * ty * (3.0 - 2.0 * ty);
+    let layer = |y: i32| value_noise(seed ^ (y as u32).wrapping_mul(0x1656_67b1), x, z);
+    let bottom = layer(y0);
+    bottom + (layer(y0 + 1) - bottom) * ty
+}
@@ -0,0 +1,9 @@
+#include "common/fullscreen.wgsl"
+
+@group(0) @binding(0) var source_texture: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@fragment
+fn fragment_main(in: FullscreenOutput) -> @location(0) vec4f {
+    return textureSample(source_texture, source_sampler, in.uv);
+}
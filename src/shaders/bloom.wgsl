@@ -0,0 +1,97 @@
+#include "common/fullscreen.wgsl"
+
+@group(0) @binding(0) var source_texture: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    filter_radius: f32,
+}
+@group(1) @binding(0) var<uniform> params: BloomParams;
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
+fn sample_source(uv: vec2f) -> vec3f {
+    return textureSampleLevel(source_texture, source_sampler, uv, 0.0).rgb;
+}
+
+// keeps what is above the threshold, with a quadratic curve over [threshold - knee, threshold + knee]
+fn bright_pass(color: vec3f) -> vec3f {
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - params.threshold + params.knee, 0.0, 2.0 * params.knee);
+    soft = soft * soft / (4.0 * params.knee + 0.00001);
+    let contribution = max(soft, brightness - params.threshold) / max(brightness, 0.00001);
+    return color * contribution;
+}
+
+// weights bright texels down so single pixels don't flicker
+fn karis_average(a: vec3f, b: vec3f, c: vec3f, d: vec3f) -> vec3f {
+    let average = (a + b + c + d) * 0.25;
+    return average / (1.0 + luminance(average));
+}
+
+// 13 taps on the source, as five overlapping 2x2 boxes
+fn downsample(uv: vec2f, karis: bool) -> vec3f {
+    let texel = 1.0 / vec2f(textureDimensions(source_texture));
+    let x = texel.x;
+    let y = texel.y;
+
+    let a = sample_source(uv + vec2f(-2.0 * x, 2.0 * y));
+    let b = sample_source(uv + vec2f(0.0, 2.0 * y));
+    let c = sample_source(uv + vec2f(2.0 * x, 2.0 * y));
+    let d = sample_source(uv + vec2f(-2.0 * x, 0.0));
+    let e = sample_source(uv);
+    let f = sample_source(uv + vec2f(2.0 * x, 0.0));
+    let g = sample_source(uv + vec2f(-2.0 * x, -2.0 * y));
+    let h = sample_source(uv + vec2f(0.0, -2.0 * y));
+    let i = sample_source(uv + vec2f(2.0 * x, -2.0 * y));
+    let j = sample_source(uv + vec2f(-x, y));
+    let k = sample_source(uv + vec2f(x, y));
+    let l = sample_source(uv + vec2f(-x, -y));
+    let m = sample_source(uv + vec2f(x, -y));
+
+    if karis {
+        return karis_average(j, k, l, m) * 0.5
+            + karis_average(a, b, d, e) * 0.125
+            + karis_average(b, c, e, f) * 0.125
+            + karis_average(d, e, g, h) * 0.125
+            + karis_average(e, f, h, i) * 0.125;
+    }
+    return e * 0.125
+        + (a + c + g + i) * 0.03125
+        + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+}
+
+@fragment
+fn prefilter_main(in: FullscreenOutput) -> @location(0) vec4f {
+    return vec4f(bright_pass(downsample(in.uv, true)), 1.0);
+}
+
+@fragment
+fn downsample_main(in: FullscreenOutput) -> @location(0) vec4f {
+    return vec4f(downsample(in.uv, false), 1.0);
+}
+
+// 3x3 tent filter, added on top of the next bigger mip by blending
+@fragment
+fn upsample_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let x = params.filter_radius;
+    let y = params.filter_radius;
+
+    var color = sample_source(in.uv) * 4.0;
+    color += (sample_source(in.uv + vec2f(0.0, y)) + sample_source(in.uv + vec2f(-x, 0.0))
+        + sample_source(in.uv + vec2f(x, 0.0)) + sample_source(in.uv + vec2f(0.0, -y))) * 2.0;
+    color += sample_source(in.uv + vec2f(-x, y)) + sample_source(in.uv + vec2f(x, y))
+        + sample_source(in.uv + vec2f(-x, -y)) + sample_source(in.uv + vec2f(x, -y));
+    return vec4f(color / 16.0, 1.0);
+}
+
+// scaled by the blend constant when added to the HDR target
+@fragment
+fn composite_main(in: FullscreenOutput) -> @location(0) vec4f {
+    return vec4f(sample_source(in.uv), 1.0);
+}
@@ -0,0 +1,6 @@
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+    // clip space to world space, e.g. to get view rays
+    inv_view_proj: mat4x4<f32>,
+}
@@ -0,0 +1,57 @@
+// see Fog in renderer_backend/environment.rs
+const FOG_DISABLED: u32 = 0u;
+const FOG_LINEAR: u32 = 1u;
+const FOG_EXPONENTIAL: u32 = 2u;
+const FOG_EXPONENTIAL_SQUARED: u32 = 3u;
+
+struct Fog {
+    mode: u32,
+    start: f32,
+    end: f32,
+    density: f32,
+    height_density: f32,
+    height_falloff: f32,
+    base_height: f32,
+}
+
+fn distance_fog(fog: Fog, distance: f32) -> f32 {
+    switch fog.mode {
+        case FOG_LINEAR: {
+            return clamp((distance - fog.start) / max(fog.end - fog.start, 0.0001), 0.0, 1.0);
+        }
+        case FOG_EXPONENTIAL: {
+            return 1.0 - exp(-fog.density * distance);
+        }
+        case FOG_EXPONENTIAL_SQUARED: {
+            let amount = fog.density * distance;
+            return 1.0 - exp(-amount * amount);
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
+
+// density decreasing exponentially with the height, integrated along the view ray
+fn height_fog(fog: Fog, camera_position: vec3f, world_position: vec3f) -> f32 {
+    if fog.mode == FOG_DISABLED || fog.height_density <= 0.0 {
+        return 0.0;
+    }
+    let ray = world_position - camera_position;
+    let distance = length(ray);
+    let camera_density = fog.height_density * exp(-fog.height_falloff * (camera_position.y - fog.base_height));
+    let rise = fog.height_falloff * ray.y;
+    // the integral tends to camera_density * distance on horizontal rays
+    var amount = camera_density * distance;
+    if abs(rise) > 0.0001 {
+        amount *= (1.0 - exp(-rise)) / rise;
+    }
+    return 1.0 - exp(-amount);
+}
+
+// fraction of the fog color to blend in at `world_position`
+fn fog_factor(fog: Fog, camera_position: vec3f, world_position: vec3f) -> f32 {
+    let distance = distance(camera_position, world_position);
+    let visibility = (1.0 - distance_fog(fog, distance)) * (1.0 - height_fog(fog, camera_position, world_position));
+    return 1.0 - visibility;
+}
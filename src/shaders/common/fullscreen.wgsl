@@ -0,0 +1,15 @@
+struct FullscreenOutput {
+    @builtin(position) position: vec4f,
+    @location(0) uv: vec2f,
+}
+
+// one triangle covering the whole screen, draw with 3 vertices and no vertex buffer
+@vertex
+fn fullscreen_vertex(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: FullscreenOutput;
+    out.position = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
@@ -0,0 +1,8 @@
+struct InstanceInput {
+    @location(5) vertex_matrix_0: vec4<f32>,
+    @location(6) vertex_matrix_1: vec4<f32>,
+    @location(7) vertex_matrix_2: vec4<f32>,
+    @location(8) vertex_matrix_3: vec4<f32>,
+	@location(9) position: vec3<f32>,
+	@location(10) scale: f32,
+};
@@ -0,0 +1,8 @@
+// see Environment in renderer_backend/environment.rs
+struct Lighting {
+    // towards the sun
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    ambient_color: vec4f,
+    fog_color: vec4f,
+}
@@ -0,0 +1,5 @@
+#include "fullscreen.wgsl"
+
+// output of the previous effect, uniforms of the effect go in group 1
+@group(0) @binding(0) var input_texture: texture_2d<f32>;
+@group(0) @binding(1) var input_sampler: sampler;
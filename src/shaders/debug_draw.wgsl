@@ -0,0 +1,27 @@
+#include "common/camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct LineInput {
+    @location(0) position: vec3f,
+    @location(1) color: vec4f,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4f,
+    @location(0) color: vec4f,
+}
+
+@vertex
+fn vertex_main(in: LineInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = camera.view_proj * vec4f(in.position, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
+    return in.color;
+}
@@ -0,0 +1,30 @@
+#include "common/fullscreen.wgsl"
+
+// the HDR target drawn by a debug view of shader.wgsl
+@group(0) @binding(0) var source_texture: texture_2d<f32>;
+
+// overdraw counts from which the heatmap is fully green, yellow and red
+const HEATMAP_STEPS: vec3f = vec3f(2.0, 4.0, 8.0);
+
+fn heatmap(count: f32) -> vec3f {
+    if count < 0.5 {
+        return vec3f(0.0);
+    }
+    if count < HEATMAP_STEPS.x {
+        return mix(vec3f(0.0, 0.1, 1.0), vec3f(0.0, 1.0, 0.2), (count - 1.0) / (HEATMAP_STEPS.x - 1.0));
+    }
+    if count < HEATMAP_STEPS.y {
+        return mix(vec3f(0.0, 1.0, 0.2), vec3f(1.0, 1.0, 0.0), (count - HEATMAP_STEPS.x) / (HEATMAP_STEPS.y - HEATMAP_STEPS.x));
+    }
+    return mix(vec3f(1.0, 1.0, 0.0), vec3f(1.0, 0.0, 0.0), clamp((count - HEATMAP_STEPS.y) / (HEATMAP_STEPS.z - HEATMAP_STEPS.y), 0.0, 1.0));
+}
+
+@fragment
+fn fragment_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let color = textureLoad(source_texture, vec2i(in.position.xy), 0);
+#ifdef HEATMAP
+    return vec4f(heatmap(color.r), 1.0);
+#else
+    return vec4f(clamp(color.rgb, vec3f(0.0), vec3f(1.0)), 1.0);
+#endif
+}
@@ -0,0 +1,60 @@
+#include "common/fullscreen.wgsl"
+
+const PI: f32 = 3.14159265;
+
+@group(0) @binding(0) var equirectangular: texture_2d<f32>;
+
+struct FaceParams {
+    face: u32,
+}
+@group(0) @binding(1) var<uniform> params: FaceParams;
+
+// direction through `uv` on a cube face, faces being ordered +X, -X, +Y, -Y, +Z, -Z
+fn face_direction(face: u32, uv: vec2f) -> vec3f {
+    let s = uv.x * 2.0 - 1.0;
+    let t = uv.y * 2.0 - 1.0;
+    switch face {
+        case 0u: { return vec3f(1.0, -t, -s); }
+        case 1u: { return vec3f(-1.0, -t, s); }
+        case 2u: { return vec3f(s, 1.0, t); }
+        case 3u: { return vec3f(s, -1.0, -t); }
+        case 4u: { return vec3f(s, -t, 1.0); }
+        default: { return vec3f(-s, -t, -1.0); }
+    }
+}
+
+// filtered by hand, 32 bit float textures aren't filterable on every device
+fn load_bilinear(uv: vec2f) -> vec3f {
+    let size = vec2i(textureDimensions(equirectangular));
+    let position = uv * vec2f(size) - 0.5;
+    let base = vec2i(floor(position));
+    let weight = fract(position);
+
+    // wraps around horizontally, clamps at the poles
+    let x0 = (base.x % size.x + size.x) % size.x;
+    let x1 = (x0 + 1) % size.x;
+    let y0 = clamp(base.y, 0, size.y - 1);
+    let y1 = clamp(base.y + 1, 0, size.y - 1);
+
+    let top = mix(
+        textureLoad(equirectangular, vec2i(x0, y0), 0).rgb,
+        textureLoad(equirectangular, vec2i(x1, y0), 0).rgb,
+        weight.x,
+    );
+    let bottom = mix(
+        textureLoad(equirectangular, vec2i(x0, y1), 0).rgb,
+        textureLoad(equirectangular, vec2i(x1, y1), 0).rgb,
+        weight.x,
+    );
+    return mix(top, bottom, weight.y);
+}
+
+@fragment
+fn fragment_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let direction = normalize(face_direction(params.face, in.uv));
+    let uv = vec2f(
+        atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI,
+    );
+    return vec4f(load_bilinear(uv), 1.0);
+}
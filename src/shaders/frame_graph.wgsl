@@ -0,0 +1,75 @@
+// one bar per frame, the oldest on the left, with the GPU time marked on top of the CPU one
+const SAMPLE_COUNT: u32 = 128u;
+
+struct FrameGraph {
+    // left, bottom, width and height in clip space
+    rect: vec4f,
+    // milliseconds at the top of the graph, and the frame budget
+    max_ms: f32,
+    budget_ms: f32,
+    sample_count: u32,
+    _padding: f32,
+    // four samples per vector, uniform arrays needing a 16 bytes stride
+    cpu_ms: array<vec4f, 32>,
+    gpu_ms: array<vec4f, 32>,
+}
+
+@group(0) @binding(0) var<uniform> graph: FrameGraph;
+
+struct VertexOutput {
+    @builtin(position) position: vec4f,
+    @location(0) uv: vec2f,
+}
+
+@vertex
+fn vertex_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var corners = array<vec2f, 6>(
+        vec2f(0.0, 0.0), vec2f(1.0, 0.0), vec2f(1.0, 1.0),
+        vec2f(0.0, 0.0), vec2f(1.0, 1.0), vec2f(0.0, 1.0),
+    );
+    let uv = corners[vertex_index];
+    var out: VertexOutput;
+    out.position = vec4f(graph.rect.xy + uv * graph.rect.zw, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn cpu_sample(index: u32) -> f32 {
+    return graph.cpu_ms[index / 4u][index % 4u];
+}
+
+fn gpu_sample(index: u32) -> f32 {
+    return graph.gpu_ms[index / 4u][index % 4u];
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
+    let background = vec4f(0.0, 0.0, 0.0, 0.5);
+    let first = SAMPLE_COUNT - graph.sample_count;
+    let column = min(u32(in.uv.x * f32(SAMPLE_COUNT)), SAMPLE_COUNT - 1u);
+    if column < first {
+        return background;
+    }
+    let index = column - first;
+    let ms = in.uv.y * graph.max_ms;
+    let pixel_ms = graph.max_ms / 200.0;
+
+    // budget line
+    if abs(ms - graph.budget_ms) < pixel_ms {
+        return vec4f(1.0, 1.0, 1.0, 0.6);
+    }
+    let gpu_ms = gpu_sample(index);
+    if gpu_ms > 0.0 && abs(ms - gpu_ms) < pixel_ms * 1.5 {
+        return vec4f(0.3, 0.6, 1.0, 1.0);
+    }
+    let cpu_ms = cpu_sample(index);
+    if ms < cpu_ms {
+        if cpu_ms <= graph.budget_ms {
+            return vec4f(0.2, 0.8, 0.2, 0.85);
+        } else if cpu_ms <= graph.budget_ms * 2.0 {
+            return vec4f(0.9, 0.8, 0.1, 0.85);
+        }
+        return vec4f(0.9, 0.2, 0.2, 0.85);
+    }
+    return background;
+}
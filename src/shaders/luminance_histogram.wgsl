@@ -0,0 +1,71 @@
+const HISTOGRAM_BINS: u32 = 256u;
+
+struct HistogramParams {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    // how much of the new average gets blended in this frame
+    adaptation: f32,
+}
+
+@group(0) @binding(0) var hdr_texture: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: HistogramParams;
+@group(0) @binding(2) var<storage, read_write> histogram: array<atomic<u32>, HISTOGRAM_BINS>;
+@group(0) @binding(3) var<storage, read_write> average_luminance: f32;
+
+var<workgroup> local_bins: array<atomic<u32>, HISTOGRAM_BINS>;
+var<workgroup> weighted_bins: array<f32, HISTOGRAM_BINS>;
+
+// bin 0 holds the pixels too dark to count, the others split the log range evenly
+fn luminance_bin(color: vec3f) -> u32 {
+    let luminance = dot(color, vec3f(0.2126, 0.7152, 0.0722));
+    if luminance < 0.005 {
+        return 0u;
+    }
+    let log_luminance = clamp(
+        (log2(luminance) - params.min_log_luminance) / params.log_luminance_range,
+        0.0,
+        1.0,
+    );
+    return u32(log_luminance * 254.0 + 1.0);
+}
+
+@compute @workgroup_size(16, 16)
+fn histogram_main(
+    @builtin(global_invocation_id) global_id: vec3u,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    atomicStore(&local_bins[local_index], 0u);
+    workgroupBarrier();
+
+    let size = textureDimensions(hdr_texture);
+    if global_id.x < size.x && global_id.y < size.y {
+        let color = textureLoad(hdr_texture, vec2i(global_id.xy), 0).rgb;
+        atomicAdd(&local_bins[luminance_bin(color)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[local_index], atomicLoad(&local_bins[local_index]));
+}
+
+@compute @workgroup_size(256)
+fn average_main(@builtin(local_invocation_index) local_index: u32) {
+    let count = atomicExchange(&histogram[local_index], 0u);
+    weighted_bins[local_index] = f32(count) * f32(local_index);
+    workgroupBarrier();
+
+    for (var stride = HISTOGRAM_BINS / 2u; stride > 0u; stride >>= 1u) {
+        if local_index < stride {
+            weighted_bins[local_index] += weighted_bins[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if local_index == 0u {
+        let size = textureDimensions(hdr_texture);
+        let lit_pixels = max(f32(size.x * size.y) - f32(count), 1.0);
+        let average_bin = weighted_bins[0] / lit_pixels - 1.0;
+        let log_average = average_bin / 254.0 * params.log_luminance_range + params.min_log_luminance;
+        let target_luminance = exp2(log_average);
+        average_luminance += (target_luminance - average_luminance) * params.adaptation;
+    }
+}
@@ -0,0 +1,18 @@
+#include "common/fullscreen.wgsl"
+
+// resolved weighted blended OIT targets, see renderer_backend/transparency.rs
+@group(0) @binding(0) var accum_texture: texture_2d<f32>;
+@group(0) @binding(1) var revealage_texture: texture_2d<f32>;
+
+// blended over the opaque scene with the alpha blend state
+@fragment
+fn fragment_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let position = vec2i(in.position.xy);
+    let revealage = textureLoad(revealage_texture, position, 0).r;
+    if revealage >= 1.0 {
+        discard;
+    }
+    let accum = textureLoad(accum_texture, position, 0);
+    let average_color = accum.rgb / clamp(accum.a, 0.0001, 50000.0);
+    return vec4f(average_color, 1.0 - revealage);
+}
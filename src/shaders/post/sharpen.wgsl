@@ -0,0 +1,18 @@
+#include "../common/post_process.wgsl"
+
+struct SharpenParams {
+    strength: f32,
+}
+@group(1) @binding(0) var<uniform> params: SharpenParams;
+
+@fragment
+fn fragment_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let texel = 1.0 / vec2f(textureDimensions(input_texture));
+    let center = textureSample(input_texture, input_sampler, in.uv);
+    let neighbors = textureSample(input_texture, input_sampler, in.uv + vec2f(texel.x, 0.0))
+        + textureSample(input_texture, input_sampler, in.uv - vec2f(texel.x, 0.0))
+        + textureSample(input_texture, input_sampler, in.uv + vec2f(0.0, texel.y))
+        + textureSample(input_texture, input_sampler, in.uv - vec2f(0.0, texel.y));
+    let sharpened = center.rgb * (1.0 + 4.0 * params.strength) - neighbors.rgb * params.strength;
+    return vec4f(max(sharpened, vec3f(0.0)), center.a);
+}
@@ -0,0 +1,17 @@
+#include "../common/post_process.wgsl"
+
+struct VignetteParams {
+    intensity: f32,
+    radius: f32,
+    softness: f32,
+}
+@group(1) @binding(0) var<uniform> params: VignetteParams;
+
+@fragment
+fn fragment_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let color = textureSample(input_texture, input_sampler, in.uv);
+    // 0 in the center, 1 in the corners
+    let distance = length(in.uv - vec2f(0.5)) * 1.41421356;
+    let darkening = smoothstep(params.radius, params.radius + params.softness, distance);
+    return vec4f(color.rgb * (1.0 - darkening * params.intensity), color.a);
+}
@@ -1,30 +1,46 @@
-struct InstanceInput {
-    @location(5) vertex_matrix_0: vec4<f32>,
-    @location(6) vertex_matrix_1: vec4<f32>,
-    @location(7) vertex_matrix_2: vec4<f32>,
-    @location(8) vertex_matrix_3: vec4<f32>,
-	@location(9) position: vec3<f32>,
-	@location(10) scale: f32,
-};
-
-struct Camera {
-    view_pos: vec4<f32>,
-    view_proj: mat4x4<f32>,
-}
+#include "common/camera.wgsl"
+#include "common/instance.wgsl"
+#include "common/lighting.wgsl"
+#include "common/fog.wgsl"
+
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
 @group(1) @binding(0) var my_texture: texture_2d<f32>;
 @group(1) @binding(1) var my_sampler: sampler;
 
+struct TextureParams {
+    lod_bias: f32,
+}
+@group(1) @binding(2) var<uniform> texture_params: TextureParams;
+
+@group(2) @binding(0) var<uniform> lighting: Lighting;
+@group(2) @binding(1) var<uniform> fog: Fog;
+
 struct VertexInput {
 	@location(0) position: vec3f,
 	@location(1) tex_coords: vec2f,
+	@location(2) normal: vec3f,
+}
+
+struct VoxelInput {
+	@location(0) position: vec3f,
+	@location(1) tex_coords: vec2f,
+	@location(2) normal: vec3f,
+	// see mesher::pack_vertex_light
+	@location(3) light: u32,
 }
 
 struct VertexOutput {
     @builtin(position) position: vec4f,
 	@location(0) tex_coords: vec2f,
+	@location(1) world_normal: vec3f,
+	@location(2) world_position: vec3f,
+	@location(3) ao: f32,
+	// sky and block light levels, from 0 to 15
+	@location(4) voxel_light: vec2f,
+	// same for every vertex of an instance, or of a chunk, see debug_color
+	@location(5) @interpolate(flat) debug_seed: vec3f,
 }
 
 @vertex
@@ -36,18 +52,175 @@ fn vertex_main(vertex: VertexInput,
         instance.vertex_matrix_2,
         instance.vertex_matrix_3,
     );
-    let instance_view_proj = camera.view_proj * instance_transform;
 
     let scaled_position = vertex.position * instance.scale;
-    let projected_position = instance_view_proj * vec4<f32>(scaled_position, 1.0);
+    let world_position = instance_transform * vec4<f32>(scaled_position, 1.0);
+    let projected_position = camera.view_proj * world_position;
 
     var out: VertexOutput;
     out.tex_coords = vertex.tex_coords;
+    out.world_normal = (instance_transform * vec4f(vertex.normal, 0.0)).xyz;
+    out.world_position = world_position.xyz;
     out.position = projected_position;
+    out.ao = 1.0;
+    out.voxel_light = vec2f(15.0, 0.0);
+    out.debug_seed = instance.position;
     return out;
 }
 
+// chunk meshes are already in world space
+@vertex
+fn voxel_vertex_main(vertex: VoxelInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = vertex.tex_coords;
+    out.world_normal = vertex.normal;
+    out.world_position = vertex.position;
+    out.position = camera.view_proj * vec4f(vertex.position, 1.0);
+    // fully occluded corners keep some of the light
+    out.ao = 0.45 + 0.55 * f32(vertex.light & 3u) / 3.0;
+    out.voxel_light = vec2f(f32((vertex.light >> 4u) & 15u), f32((vertex.light >> 8u) & 15u));
+    let chunk = floor(vertex.position.xz / CHUNK_SIZE);
+    out.debug_seed = vec3f(chunk.x, 0.0, chunk.y);
+    return out;
+}
+
+// Variants, selected with pipeline::Builder::add_define:
+//   CUTOUT   discards texels below ALPHA_CUTOFF
+//   ALPHA_TO_COVERAGE   with CUTOUT, outputs a sharpened alpha for alpha to coverage instead
+//   OIT      writes the weighted blended OIT targets instead of a color
+//   CULLED   tints the color, for the instances culled by a frozen frustum
+//   DEBUG_VIEW = n   draws the debug_view::DebugView of discriminant n instead of shading
+const ALPHA_CUTOFF: f32 = 0.5;
+const BLOCK_LIGHT_COLOR: vec3f = vec3f(1.0, 0.78, 0.5);
+// keeps unlit caves from being pitch black
+const MIN_LIGHT: f32 = 0.01;
+const CULLED_TINT: vec3f = vec3f(1.0, 0.1, 0.6);
+// world::CHUNK_SIZE
+const CHUNK_SIZE: f32 = 16.0;
+
+// perceived brightness of a light level, each level below 15 dimming it further
+fn light_curve(level: f32) -> f32 {
+    let ratio = clamp(level / 15.0, 0.0, 1.0);
+    return ratio / (4.0 - 3.0 * ratio);
+}
+
+fn shade(frag_data: VertexOutput) -> vec4f {
+    let albedo = textureSampleBias(my_texture, my_sampler, frag_data.tex_coords, texture_params.lod_bias);
+    var alpha = albedo.a;
+#ifdef CUTOUT
+#ifdef ALPHA_TO_COVERAGE
+    // sharpened around the cutoff, the coverage then fading over about a pixel
+    alpha = saturate((albedo.a - ALPHA_CUTOFF) / max(fwidth(albedo.a), 0.0001) + 0.5);
+    if alpha <= 0.0 {
+        discard;
+    }
+#else
+    if alpha < ALPHA_CUTOFF {
+        discard;
+    }
+#endif
+#endif
+    let diffuse = max(dot(normalize(frag_data.world_normal), lighting.sun_direction.xyz), 0.0);
+    let sky = light_curve(frag_data.voxel_light.x);
+    let block = light_curve(frag_data.voxel_light.y);
+    let environment_light = (lighting.ambient_color.rgb + lighting.sun_color.rgb * diffuse) * sky;
+    let light = (environment_light + BLOCK_LIGHT_COLOR * block + MIN_LIGHT) * frag_data.ao;
+    let fog_amount = fog_factor(fog, camera.view_pos.xyz, frag_data.world_position);
+    let color = mix(albedo.rgb * light, lighting.fog_color.rgb, fog_amount);
+    return vec4f(color, alpha);
+}
+
+#ifdef DEBUG_VIEW
+const VIEW_WIREFRAME: u32 = 1u;
+const VIEW_NORMALS: u32 = 2u;
+const VIEW_DEPTH: u32 = 3u;
+const VIEW_UV_CHECKER: u32 = 4u;
+const VIEW_INSTANCE_COLOR: u32 = 5u;
+const VIEW_OVERDRAW: u32 = 6u;
+const WIREFRAME_COLOR: vec3f = vec3f(0.1, 2.0, 0.3);
+const MISSING_NORMAL_COLOR: vec3f = vec3f(1.0, 0.0, 1.0);
+const UV_CHECKER_CELLS: f32 = 8.0;
+
+// distance of the point at `depth` on the view axis over the one of the far plane, both
+// unprojected the same way, which keeps it independent of the projection
+fn linear_depth(depth: f32) -> f32 {
+    let point = camera.inv_view_proj * vec4f(0.0, 0.0, depth, 1.0);
+    let far = camera.inv_view_proj * vec4f(0.0, 0.0, 1.0, 1.0);
+    let eye = camera.view_pos.xyz;
+    return distance(point.xyz / point.w, eye) / distance(far.xyz / far.w, eye);
+}
+
+// hash without sine, Dave Hoskins
+fn hash_color(seed: vec3f) -> vec3f {
+    var p = fract(seed * vec3f(0.1031, 0.1030, 0.0973));
+    p += dot(p, p.yxz + 33.33);
+    return fract((p.xxy + p.yxx) * p.zyx);
+}
+
+fn debug_color(frag_data: VertexOutput) -> vec3f {
+    switch DEBUG_VIEW {
+        case VIEW_WIREFRAME: {
+            return WIREFRAME_COLOR;
+        }
+        case VIEW_NORMALS: {
+            if length(frag_data.world_normal) < 0.0001 {
+                return MISSING_NORMAL_COLOR;
+            }
+            return normalize(frag_data.world_normal) * 0.5 + 0.5;
+        }
+        case VIEW_DEPTH: {
+            return vec3f(linear_depth(frag_data.position.z));
+        }
+        case VIEW_UV_CHECKER: {
+            let cell = floor(frag_data.tex_coords * UV_CHECKER_CELLS);
+            let dark = (cell.x + cell.y) - 2.0 * floor((cell.x + cell.y) / 2.0);
+            let uv_color = vec3f(fract(frag_data.tex_coords), 1.0);
+            return uv_color * (1.0 - 0.6 * dark);
+        }
+        case VIEW_INSTANCE_COLOR: {
+            return hash_color(frag_data.debug_seed) * 0.8 + 0.2;
+        }
+        case VIEW_OVERDRAW: {
+            // counted by the additive blending, see debug_view.wgsl
+            return vec3f(1.0);
+        }
+        default: {
+            return vec3f(0.0);
+        }
+    }
+}
+#endif
+
+#ifdef OIT
+struct OitOutput {
+    @location(0) accum: vec4f,
+    @location(1) revealage: f32,
+}
+
+@fragment
+fn fragment_main(frag_data: VertexOutput) -> OitOutput {
+    let color = shade(frag_data);
+    // favors close fragments, McGuire and Bavoil 2013, equation 7
+    let distance = distance(camera.view_pos.xyz, frag_data.world_position);
+    let weight = color.a * clamp(10.0 / (0.00001 + pow(distance / 5.0, 2.0) + pow(distance / 200.0, 6.0)), 0.01, 3000.0);
+
+    var out: OitOutput;
+    out.accum = vec4f(color.rgb * color.a, color.a) * weight;
+    out.revealage = color.a;
+    return out;
+}
+#else
 @fragment
 fn fragment_main(frag_data: VertexOutput) -> @location(0) vec4f {
-    return textureSample(my_texture, my_sampler, frag_data.tex_coords);
-}
\ No newline at end of file
+#ifdef DEBUG_VIEW
+    return vec4f(debug_color(frag_data), 1.0);
+#else
+#ifdef CULLED
+    let color = shade(frag_data);
+    return vec4f(mix(color.rgb, CULLED_TINT, 0.6), color.a);
+#else
+    return shade(frag_data);
+#endif
+#endif
+}
+#endif
@@ -0,0 +1,143 @@
+#include "common/camera.wgsl"
+
+const PI: f32 = 3.14159265;
+
+const SKY_PROCEDURAL: u32 = 0u;
+const SKY_CUBEMAP: u32 = 1u;
+const SKY_GRADIENT: u32 = 2u;
+
+// in meters, the viewer stands on the ground
+const PLANET_RADIUS: f32 = 6371e3;
+const ATMOSPHERE_RADIUS: f32 = 6471e3;
+const RAYLEIGH_SCATTERING: vec3f = vec3f(5.5e-6, 13.0e-6, 22.4e-6);
+const RAYLEIGH_SCALE_HEIGHT: f32 = 8e3;
+const MIE_SCATTERING: f32 = 21e-6;
+const MIE_SCALE_HEIGHT: f32 = 1.2e3;
+const MIE_ANISOTROPY: f32 = 0.758;
+const PRIMARY_STEPS: i32 = 16;
+const LIGHT_STEPS: i32 = 8;
+// cosine of the angular radius of the sun disk
+const SUN_DISK_COS: f32 = 0.99996;
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct SkyParams {
+    sun_direction: vec3f,
+    sun_intensity: f32,
+    horizon_color: vec3f,
+    mode: u32,
+    zenith_color: vec3f,
+}
+@group(1) @binding(0) var<uniform> sky: SkyParams;
+@group(1) @binding(1) var sky_texture: texture_cube<f32>;
+@group(1) @binding(2) var sky_sampler: sampler;
+
+struct SkyOutput {
+    @builtin(position) position: vec4f,
+    // world space view ray, divided by `w` in the fragment stage
+    @location(0) ray: vec3f,
+    @location(1) w: f32,
+}
+
+// fullscreen triangle on the far plane, so only pixels left at the cleared depth are covered
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> SkyOutput {
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+    let clip = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 1.0, 1.0);
+    let world = camera.inv_view_proj * clip;
+
+    var out: SkyOutput;
+    out.position = clip;
+    out.ray = world.xyz - camera.view_pos.xyz * world.w;
+    out.w = world.w;
+    return out;
+}
+
+// distances to the two intersections, the first being negative when starting inside
+fn ray_sphere(origin: vec3f, direction: vec3f, radius: f32) -> vec2f {
+    let b = dot(origin, direction);
+    let c = dot(origin, origin) - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return vec2f(1e5, -1e5);
+    }
+    let root = sqrt(discriminant);
+    return vec2f(-b - root, -b + root);
+}
+
+// single scattering of the sun light along the view ray, marched through the atmosphere
+fn atmosphere(direction: vec3f, sun: vec3f) -> vec3f {
+    let origin = vec3f(0.0, PLANET_RADIUS + 1.0, 0.0);
+    var distance = ray_sphere(origin, direction, ATMOSPHERE_RADIUS).y;
+    let ground = ray_sphere(origin, direction, PLANET_RADIUS);
+    if ground.x > 0.0 {
+        distance = ground.x;
+    }
+    let step_size = distance / f32(PRIMARY_STEPS);
+
+    var rayleigh_depth = 0.0;
+    var mie_depth = 0.0;
+    var rayleigh_total = vec3f(0.0);
+    var mie_total = vec3f(0.0);
+
+    for (var i = 0; i < PRIMARY_STEPS; i++) {
+        let position = origin + direction * (f32(i) + 0.5) * step_size;
+        let height = length(position) - PLANET_RADIUS;
+        let rayleigh_step = exp(-height / RAYLEIGH_SCALE_HEIGHT) * step_size;
+        let mie_step = exp(-height / MIE_SCALE_HEIGHT) * step_size;
+        rayleigh_depth += rayleigh_step;
+        mie_depth += mie_step;
+
+        let light_step_size = ray_sphere(position, sun, ATMOSPHERE_RADIUS).y / f32(LIGHT_STEPS);
+        var light_rayleigh_depth = 0.0;
+        var light_mie_depth = 0.0;
+        for (var j = 0; j < LIGHT_STEPS; j++) {
+            let light_position = position + sun * (f32(j) + 0.5) * light_step_size;
+            let light_height = length(light_position) - PLANET_RADIUS;
+            light_rayleigh_depth += exp(-light_height / RAYLEIGH_SCALE_HEIGHT) * light_step_size;
+            light_mie_depth += exp(-light_height / MIE_SCALE_HEIGHT) * light_step_size;
+        }
+
+        let attenuation = exp(-(MIE_SCATTERING * (mie_depth + light_mie_depth)
+            + RAYLEIGH_SCATTERING * (rayleigh_depth + light_rayleigh_depth)));
+        rayleigh_total += rayleigh_step * attenuation;
+        mie_total += mie_step * attenuation;
+    }
+
+    let mu = dot(direction, sun);
+    let g = MIE_ANISOTROPY;
+    let rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    let mie_phase = 3.0 / (8.0 * PI) * ((1.0 - g * g) * (1.0 + mu * mu))
+        / ((2.0 + g * g) * pow(1.0 + g * g - 2.0 * mu * g, 1.5));
+    var color = rayleigh_phase * RAYLEIGH_SCATTERING * rayleigh_total
+        + mie_phase * MIE_SCATTERING * mie_total;
+
+    if mu > SUN_DISK_COS && ground.x < 0.0 {
+        let transmittance = exp(-(MIE_SCATTERING * mie_depth + RAYLEIGH_SCATTERING * rayleigh_depth));
+        color += transmittance;
+    }
+    return color * sky.sun_intensity;
+}
+
+// horizon to zenith, darkened below the horizon
+fn gradient(direction: vec3f) -> vec3f {
+    let elevation = direction.y;
+    if elevation < 0.0 {
+        return sky.horizon_color * mix(1.0, 0.3, min(-elevation * 4.0, 1.0));
+    }
+    return mix(sky.horizon_color, sky.zenith_color, sqrt(elevation));
+}
+
+@fragment
+fn fragment_main(in: SkyOutput) -> @location(0) vec4f {
+    let direction = normalize(in.ray / in.w);
+    let cubemap = textureSample(sky_texture, sky_sampler, direction).rgb;
+    if sky.mode == SKY_CUBEMAP {
+        return vec4f(cubemap, 1.0);
+    }
+    if sky.mode == SKY_GRADIENT {
+        return vec4f(gradient(direction), 1.0);
+    }
+    return vec4f(atmosphere(direction, normalize(sky.sun_direction)), 1.0);
+}
@@ -0,0 +1,37 @@
+// glyph quads positioned in pixels from the top left corner of the screen
+struct Screen {
+    size: vec2f,
+}
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(1) @binding(0) var font_texture: texture_2d<f32>;
+@group(1) @binding(1) var font_sampler: sampler;
+
+struct TextInput {
+    @location(0) position: vec2f,
+    @location(1) tex_coords: vec2f,
+    @location(2) color: vec4f,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4f,
+    @location(0) tex_coords: vec2f,
+    @location(1) color: vec4f,
+}
+
+@vertex
+fn vertex_main(in: TextInput) -> VertexOutput {
+    let clip = in.position / screen.size * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0);
+    var out: VertexOutput;
+    out.position = vec4f(clip, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
+    // the atlas is white, its alpha being the glyph coverage
+    let coverage = textureSample(font_texture, font_sampler, in.tex_coords).a;
+    return vec4f(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,80 @@
+#include "common/fullscreen.wgsl"
+
+const TONE_MAPPING_REINHARD: u32 = 0u;
+const TONE_MAPPING_ACES: u32 = 1u;
+const TONE_MAPPING_AGX: u32 = 2u;
+
+struct ToneMapParams {
+    exposure: f32,
+    tone_mapping: u32,
+    auto_exposure: u32,
+}
+
+@group(0) @binding(0) var hdr_texture: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: ToneMapParams;
+// written by luminance_histogram.wgsl
+@group(0) @binding(2) var<storage, read> average_luminance: f32;
+
+fn reinhard(color: vec3f) -> vec3f {
+    return color / (vec3f(1.0) + color);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3f) -> vec3f {
+    let a = color * (2.51 * color + 0.03);
+    let b = color * (2.43 * color + 0.59) + 0.14;
+    return clamp(a / b, vec3f(0.0), vec3f(1.0));
+}
+
+// minimal AgX, polynomial approximation of the default contrast curve
+fn agx_contrast(x: vec3f) -> vec3f {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+fn agx(color: vec3f) -> vec3f {
+    let inset = mat3x3f(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    );
+    let outset = mat3x3f(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var encoded = inset * max(color, vec3f(1e-10));
+    encoded = clamp(log2(encoded), vec3f(min_ev), vec3f(max_ev));
+    encoded = (encoded - min_ev) / (max_ev - min_ev);
+    encoded = outset * agx_contrast(encoded);
+    // AgX outputs display encoded values, the sRGB surface expects linear ones
+    return pow(max(encoded, vec3f(0.0)), vec3f(2.2));
+}
+
+@fragment
+fn fragment_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let hdr = textureLoad(hdr_texture, vec2i(in.position.xy), 0);
+
+    var exposure = params.exposure;
+    if params.auto_exposure != 0u {
+        // aim for the average luminance to land on middle grey
+        exposure *= 0.18 / max(average_luminance, 1e-4);
+    }
+    let color = hdr.rgb * exposure;
+
+    switch params.tone_mapping {
+        case TONE_MAPPING_ACES: {
+            return vec4f(aces(color), 1.0);
+        }
+        case TONE_MAPPING_AGX: {
+            return vec4f(agx(color), 1.0);
+        }
+        default: {
+            return vec4f(reinhard(color), 1.0);
+        }
+    }
+}